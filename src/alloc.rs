@@ -10,9 +10,18 @@
 //!
 //! Call the `exit_boot_services` function before exiting UEFI boot services.
 //! Failure to do so will turn subsequent allocation into undefined behaviour.
+//!
+//! # Statistics
+//!
+//! When the `alloc_stats` feature is enabled, the allocator keeps track of the
+//! number of bytes it has handed out and taken back, which can be queried with
+//! the `stats` function. This is useful for tracking down memory leaks. The
+//! number of allocations is also counted, see `allocation_count`.
 
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr::{self, NonNull};
+#[cfg(feature = "alloc_stats")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::prelude::*;
 use crate::table::boot::{BootServices, MemoryType};
@@ -47,6 +56,76 @@ pub fn exit_boot_services() {
     }
 }
 
+/// Number of bytes handed out by the allocator since initialization
+#[cfg(feature = "alloc_stats")]
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of bytes given back to the allocator since initialization
+#[cfg(feature = "alloc_stats")]
+static FREED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of successful calls to `alloc` since initialization
+#[cfg(feature = "alloc_stats")]
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Highest number of bytes that were simultaneously outstanding
+#[cfg(feature = "alloc_stats")]
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Snapshot of the allocator's statistics.
+#[cfg(feature = "alloc_stats")]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct AllocStats {
+    /// Total number of bytes allocated since initialization.
+    pub allocated: usize,
+    /// Total number of bytes freed since initialization.
+    pub freed: usize,
+    /// Number of bytes which are currently allocated.
+    pub outstanding: usize,
+    /// Highest number of bytes which were allocated at the same time.
+    pub peak: usize,
+}
+
+/// Query the allocator's statistics.
+///
+/// The counters are updated with relaxed atomics, so the values of a snapshot
+/// taken while another processor is allocating may be slightly inconsistent.
+#[cfg(feature = "alloc_stats")]
+pub fn stats() -> AllocStats {
+    let allocated = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let freed = FREED_BYTES.load(Ordering::Relaxed);
+    AllocStats {
+        allocated,
+        freed,
+        outstanding: allocated.saturating_sub(freed),
+        peak: PEAK_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+/// Query the number of allocations performed since initialization.
+///
+/// Snapshotting this counter before and after running some code is a simple
+/// way to check that it does not allocate.
+#[cfg(feature = "alloc_stats")]
+pub fn allocation_count() -> u64 {
+    ALLOCATION_COUNT.load(Ordering::Relaxed) as u64
+}
+
+/// Record a successful allocation of `size` bytes
+#[cfg(feature = "alloc_stats")]
+fn record_alloc(size: usize) {
+    ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+    let allocated = ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    let outstanding = allocated.saturating_sub(FREED_BYTES.load(Ordering::Relaxed));
+    PEAK_BYTES.fetch_max(outstanding, Ordering::Relaxed);
+}
+
+/// Record the release of an allocation of `size` bytes
+#[cfg(feature = "alloc_stats")]
+fn record_dealloc(size: usize) {
+    FREED_BYTES.fetch_add(size, Ordering::Relaxed);
+}
+
 /// Allocator which uses the UEFI pool allocation functions.
 ///
 /// Only valid for as long as the UEFI boot services are available.
@@ -59,7 +138,7 @@ unsafe impl GlobalAlloc for Allocator {
         let size = layout.size();
         let align = layout.align();
 
-        if align > 8 {
+        let ptr = if align > 8 {
             // allocate more space for alignment
             let ptr = if let Ok(ptr) = boot_services()
                 .as_ref()
@@ -85,7 +164,16 @@ unsafe impl GlobalAlloc for Allocator {
                 .allocate_pool(mem_ty, size)
                 .warning_as_error()
                 .unwrap_or(ptr::null_mut())
+        };
+
+        #[cfg(feature = "alloc_stats")]
+        {
+            if !ptr.is_null() {
+                record_alloc(size);
+            }
         }
+
+        ptr
     }
 
     unsafe fn dealloc(&self, mut ptr: *mut u8, layout: Layout) {
@@ -97,6 +185,9 @@ unsafe impl GlobalAlloc for Allocator {
             .free_pool(ptr)
             .warning_as_error()
             .unwrap();
+
+        #[cfg(feature = "alloc_stats")]
+        record_dealloc(layout.size());
     }
 }
 
@@ -11,11 +11,55 @@
 //!
 //! The last part also means that some Unicode characters might not be
 //! supported by the UEFI console. Don't expect emoji output support.
+//!
+//! # Deferred mode
+//!
+//! Console output is slow, which is a problem when logging from time-critical
+//! code. In deferred mode, enabled with `Logger::set_deferred`, records are
+//! stored in a 4 KiB buffer and only written out when the logger is flushed.
+//! The buffer is allocated from the pool when the mode is first enabled. If
+//! a drain event was configured with `Logger::set_drain_event`, it is
+//! signaled whenever a record is buffered, so that its notification function
+//! can flush the logger once the task priority level drops.
+//!
+//! # Rate limiting
+//!
+//! To keep a misbehaving loop from flooding the console, the number of records
+//! written per time interval can be limited with `Logger::set_rate_limit`.
+//! Records over the limit are dropped, and the number of dropped records is
+//! reported once the next interval starts. This needs a clock, which must be
+//! provided with `Logger::set_clock`.
+//!
+//! # NVRAM sink
+//!
+//! On machines without a serial port, a copy of the log can be kept in a UEFI
+//! variable with `Logger::set_nvram_sink`, to be read back after a reboot.
+//! The variable holds the most recent 16 KiB of text, older lines being
+//! dropped as new ones come in, and the buffer holding that text is only
+//! allocated once the sink is set. To limit the wear of the flash memory, the
+//! variable is only written once enough new text has accumulated and when
+//! the logger is flushed, and only at task priority levels where variables
+//! may be written.
 
 use crate::proto::console::text::Output;
+use crate::table::boot::{BootServices, MemoryType, Tpl};
+use crate::table::runtime::{RuntimeServices, VariableAttributes};
+use crate::{CStr16, Event, Guid};
 
+use core::cell::{Cell, UnsafeCell};
 use core::fmt::{self, Write};
 use core::ptr::NonNull;
+use core::time::Duration;
+use core::{slice, str};
+
+/// Size of the buffer holding the records of the deferred mode
+const DEFERRED_BUFFER_SIZE: usize = 4096;
+
+/// Size of the log kept by the NVRAM sink
+const NVRAM_LOG_SIZE: usize = 16 * 1024;
+
+/// Amount of new text after which the NVRAM sink's variable is written
+const NVRAM_WRITE_THRESHOLD: usize = 4096;
 
 /// Logging implementation which writes to a UEFI output stream.
 ///
@@ -23,7 +67,50 @@ use core::ptr::NonNull;
 /// `disable` method before exiting UEFI boot services in order to prevent
 /// undefined behaviour from inadvertent logging.
 pub struct Logger {
-    writer: Option<NonNull<Output<'static>>>,
+    writer: Cell<Option<NonNull<Output<'static>>>>,
+    deferred: Cell<bool>,
+    drain: Cell<Option<(NonNull<BootServices>, Event)>>,
+    buffer: UnsafeCell<DeferredBuffer>,
+    lost: Cell<u32>,
+    clock: Cell<Option<fn() -> Duration>>,
+    rate_limit: Cell<Option<RateLimit>>,
+    nvram: Cell<Option<NvramSink>>,
+    nvram_log: UnsafeCell<NvramLog>,
+    nvram_busy: Cell<bool>,
+    module_display: Cell<ModuleDisplay>,
+    indent: Cell<usize>,
+    sink: Cell<Option<NonNull<dyn fmt::Write>>>,
+    error_hook: Cell<Option<fn()>>,
+}
+
+/// How the origin of records is shown, see `Logger::set_module_display`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ModuleDisplay {
+    /// Show the full target of records, usually their module path.
+    Full,
+    /// Only show the last component of the target, such as `nvme` for
+    /// `my_loader::drivers::storage::nvme`.
+    Short,
+    /// Do not show the origin of records.
+    None,
+}
+
+/// Variable written by the NVRAM sink
+#[derive(Clone, Copy)]
+struct NvramSink {
+    runtime_services: NonNull<RuntimeServices>,
+    name: &'static CStr16,
+    vendor: Guid,
+}
+
+/// State of the rate limiter
+#[derive(Clone, Copy)]
+struct RateLimit {
+    max_per_interval: u32,
+    interval: Duration,
+    interval_start: Duration,
+    count: u32,
+    suppressed: u32,
 }
 
 impl Logger {
@@ -38,47 +125,548 @@ impl Logger {
     /// application has exited the boot services stage.
     pub unsafe fn new(output: &mut Output) -> Self {
         Logger {
-            writer: NonNull::new(output as *const _ as *mut _),
+            writer: Cell::new(NonNull::new(output as *const _ as *mut _)),
+            deferred: Cell::new(false),
+            drain: Cell::new(None),
+            buffer: UnsafeCell::new(DeferredBuffer::new()),
+            lost: Cell::new(0),
+            clock: Cell::new(None),
+            rate_limit: Cell::new(None),
+            nvram: Cell::new(None),
+            nvram_log: UnsafeCell::new(NvramLog::new()),
+            nvram_busy: Cell::new(false),
+            module_display: Cell::new(ModuleDisplay::None),
+            indent: Cell::new(0),
+            sink: Cell::new(None),
+            error_hook: Cell::new(None),
         }
     }
 
     /// Disable the logger
-    pub fn disable(&mut self) {
-        self.writer = None;
+    ///
+    /// Records which are still buffered by the deferred mode or which were not
+    /// written to the NVRAM sink yet are discarded, call `flush` first if they
+    /// should be written out.
+    pub fn disable(&self) {
+        self.writer.set(None);
+        self.drain.set(None);
+        self.nvram.set(None);
+    }
+
+    /// Write to another output from now on.
+    ///
+    /// Records which are buffered by the deferred mode are written to the
+    /// previous output first.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as for `new` apply to the new output.
+    pub unsafe fn set_output(&self, output: &mut Output) {
+        self.flush_deferred();
+        if self.writer.get().is_some() {
+            self.writer.set(NonNull::new(output as *const _ as *mut _));
+        }
+    }
+
+    /// Enable or disable the deferred mode.
+    ///
+    /// In deferred mode, records are buffered instead of being written to the
+    /// output right away. When the buffer is full, it is written out along
+    /// with the record which did not fit. Above the `NOTIFY` task priority
+    /// level, where the output may not be used, that record is dropped
+    /// instead, and the number of dropped records is reported by the next
+    /// flush. Disabling the deferred mode flushes the buffer.
+    ///
+    /// The buffer is allocated from `boot_services` the first time the mode
+    /// is enabled, and kept afterwards. If this fails, the error is returned
+    /// and the mode stays disabled.
+    pub fn set_deferred(&self, boot_services: &BootServices, deferred: bool) -> crate::Result {
+        if deferred && unsafe { (*self.buffer.get()).data.is_empty() } {
+            let data = allocate_buffer(boot_services, DEFERRED_BUFFER_SIZE)?.log();
+            let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+            unsafe { (*self.buffer.get()).data = data };
+        }
+
+        self.deferred.set(deferred);
+        if !deferred {
+            log::Log::flush(self);
+        }
+        Ok(().into())
+    }
+
+    /// Set the event to be signaled whenever a record is buffered in deferred
+    /// mode, or `None` to stop signaling it.
+    ///
+    /// The event should have a `NOTIFY_SIGNAL` notification function
+    /// which flushes this logger. Its notification task priority level should
+    /// be `Tpl::CALLBACK`, so that the output is written once the code which
+    /// logged the records runs at a lower priority again.
+    ///
+    /// # Safety
+    ///
+    /// The boot services must remain available for as long as the event is
+    /// set, the `disable` method clears it.
+    pub unsafe fn set_drain_event(&self, boot_services: &BootServices, event: Option<Event>) {
+        self.drain
+            .set(event.map(|event| (NonNull::from(boot_services), event)));
+    }
+
+    /// Keep a copy of the log in the UEFI variable `name` of the `vendor`
+    /// namespace, or stop doing so if `sink` is `None`.
+    ///
+    /// The variable is non-volatile, and holds the most recent 16 KiB of text,
+    /// unless the log was given other storage with `set_nvram_log_storage`.
+    /// It is written once 4 KiB of new text have been logged, and when the
+    /// logger is flushed. Records which were logged before this call are not
+    /// part of it.
+    ///
+    /// The buffer holding the text is allocated from `boot_services` the first
+    /// time a sink is set, unless the log already has storage. If this fails,
+    /// the error is returned and the sink is left alone.
+    ///
+    /// # Safety
+    ///
+    /// The runtime services must remain usable at their current address for
+    /// as long as the sink is set, the `disable` method clears it.
+    pub unsafe fn set_nvram_sink(
+        &self,
+        boot_services: &BootServices,
+        runtime_services: &RuntimeServices,
+        sink: Option<(&'static CStr16, Guid)>,
+    ) -> crate::Result {
+        if sink.is_some() && (*self.nvram_log.get()).data().is_empty() {
+            let data = allocate_buffer(boot_services, NVRAM_LOG_SIZE)?.log();
+            let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+            (*self.nvram_log.get()).allocated = Some((NonNull::from(boot_services), data));
+        }
+
+        self.nvram.set(sink.map(|(name, vendor)| NvramSink {
+            runtime_services: NonNull::from(runtime_services),
+            name,
+            vendor,
+        }));
+        Ok(().into())
+    }
+
+    /// Discard the text kept by the NVRAM sink so far.
+    ///
+    /// The variable is emptied the next time it is written, so that it only
+    /// holds the text logged after this call.
+    pub fn clear_nvram_log(&self) {
+        let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+
+        // The variable is being written from the log, which must stay intact
+        if self.nvram_busy.get() {
+            return;
+        }
+
+        let log = unsafe { &mut *self.nvram_log.get() };
+        let len = log.len;
+        log.data_mut()[..len].fill(0);
+        log.dirty = usize::from(len > 0);
+        log.len = 0;
+    }
+
+    /// Keep the text of the NVRAM sink in `storage` from now on, instead of
+    /// the 16 KiB buffer allocated by `set_nvram_sink`.
+    ///
+    /// The most recent lines of text which fit in the new storage are moved
+    /// into it, and the allocated buffer is freed. On success, returns the
+    /// storage set by the previous call, if any, which the logger no longer
+    /// uses. If the variable is being written at the time of the call,
+    /// `storage` is handed back as the error.
+    ///
+    /// Note that firmware limits the size of variables, the sink's variable
+    /// may thus fail to be written if `storage` is too large.
+    pub fn set_nvram_log_storage(
+        &self,
+        storage: &'static mut [u8],
+    ) -> Result<Option<&'static mut [u8]>, &'static mut [u8]> {
+        let guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+
+        // The variable is being written from the log, which must stay intact
+        if self.nvram_busy.get() {
+            return Err(storage);
+        }
+
+        let log = unsafe { &mut *self.nvram_log.get() };
+        let text = &log.data()[..log.len];
+
+        // Keep the most recent whole lines which fit
+        let excess = text.len().saturating_sub(storage.len());
+        let cut = if excess > 0 {
+            text[excess..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(text.len(), |pos| excess + pos + 1)
+        } else {
+            0
+        };
+        let kept = text.len() - cut;
+        storage[..kept].copy_from_slice(&text[cut..]);
+        storage[kept..].fill(0);
+
+        if cut > 0 {
+            // The variable holds text which is no longer part of the log
+            log.dirty = log.dirty.max(1);
+        }
+        log.len = kept;
+        let previous = log.external.replace(storage);
+        let allocated = log.allocated.take();
+        drop(guard);
+
+        // Boot services are still available as long as the logger is enabled
+        if let Some((bt, buffer)) = allocated {
+            if self.writer.get().is_some() {
+                let _ = unsafe { bt.as_ref() }.free_pool(buffer.as_mut_ptr());
+            }
+        }
+        Ok(previous)
+    }
+
+    /// Write out the records buffered by the deferred mode.
+    ///
+    /// Unlike `flush`, this does not write the NVRAM sink's variable, which
+    /// makes it suitable for frequent calls such as the drain event's. The
+    /// output may not be used above the `NOTIFY` task priority level, the
+    /// records thus stay buffered if this is called from there.
+    pub fn flush_deferred(&self) {
+        self.try_flush_deferred();
+    }
+
+    /// Write out the records buffered by the deferred mode, if the current
+    /// task priority level allows it
+    ///
+    /// Returns whether the buffer was emptied.
+    fn try_flush_deferred(&self) -> bool {
+        let mut ptr = match self.writer.get() {
+            Some(ptr) => ptr,
+            None => return false,
+        };
+        if self.current_tpl().map_or(false, |tpl| tpl > Tpl::NOTIFY) {
+            return false;
+        }
+
+        // Write the records out in place, at a level where the output may
+        // still be used but notification functions cannot log meanwhile
+        let _guard = self.raise_tpl(Tpl::NOTIFY);
+        let buffer = unsafe { &mut *self.buffer.get() };
+        let len = buffer.len;
+        buffer.len = 0;
+        let lost = self.lost.replace(0);
+
+        // The buffer only ever contains complete UTF-8 strings
+        let text = unsafe { str::from_utf8_unchecked(&buffer.data[..len]) };
+        let writer = unsafe { ptr.as_mut() };
+        check_result(writer.write_str(text));
+        if lost > 0 {
+            check_result(DecoratedLog::write(
+                writer,
+                log::Level::Warn,
+                self.indent.get(),
+                &format_args!("{} messages lost", lost),
+            ));
+        }
+        true
+    }
+
+    /// Choose how the origin of records is shown.
+    ///
+    /// Unless it is `ModuleDisplay::None`, which is the default, the target
+    /// of each record is written in brackets in front of its message. The
+    /// target is the module path of the code which logged the record, unless
+    /// another one was specified.
+    pub fn set_module_display(&self, mode: ModuleDisplay) {
+        self.module_display.set(mode);
+    }
+
+    /// Indent the lines of the following records by `width` spaces, after
+    /// their log level.
+    pub fn set_indent(&self, width: usize) {
+        self.indent.set(width);
+    }
+
+    /// Also write records to `sink`, or stop doing so if it is `None`.
+    ///
+    /// The sink receives records as they are logged, with the same decoration
+    /// as the output, even in deferred mode. It is not written to once the
+    /// logger is disabled, and errors it reports are ignored.
+    pub fn set_sink(&self, sink: Option<&'static mut dyn fmt::Write>) {
+        self.sink.set(sink.map(NonNull::from));
+    }
+
+    /// Call `hook` after each record at the `Error` level, or stop doing so if
+    /// it is `None`.
+    ///
+    /// The logger is flushed before the hook is called, so that the record is
+    /// written out even if the hook does not return, e.g. to fail a test run.
+    pub fn set_error_hook(&self, hook: Option<fn()>) {
+        self.error_hook.set(hook);
+    }
+
+    /// Set the clock used by the rate limiter, or `None` to remove it.
+    ///
+    /// The clock returns the time elapsed since an arbitrary point in the
+    /// past. It may be called from any task priority level.
+    pub fn set_clock(&self, clock: Option<fn() -> Duration>) {
+        self.clock.set(clock);
+    }
+
+    /// Limit the number of records written per `interval` to `max_per_interval`.
+    ///
+    /// Once the limit is reached, records are dropped until the interval is
+    /// over, and a summary of the number of dropped records is written at the
+    /// start of the next interval. A zero `interval` disables rate limiting,
+    /// which is the default. Rate limiting has no effect if no clock was set.
+    pub fn set_rate_limit(&self, max_per_interval: u32, interval: Duration) {
+        let limit = if interval == Duration::default() {
+            None
+        } else {
+            Some(RateLimit {
+                max_per_interval,
+                interval,
+                interval_start: self.clock.get().map(|clock| clock()).unwrap_or_default(),
+                count: 0,
+                suppressed: 0,
+            })
+        };
+        self.rate_limit.set(limit);
+    }
+
+    /// Account for a new record in the rate limiter
+    ///
+    /// Returns whether the record may be written, and the number of records
+    /// dropped during the previous interval which must be reported.
+    fn check_rate_limit(&self) -> (bool, u32) {
+        let (mut limit, clock) = match (self.rate_limit.get(), self.clock.get()) {
+            (Some(limit), Some(clock)) => (limit, clock),
+            _ => return (true, 0),
+        };
+
+        // Start a new interval if the current one is over, or if the clock
+        // went backwards.
+        let now = clock();
+        let mut report = 0;
+        if now < limit.interval_start || now - limit.interval_start >= limit.interval {
+            report = limit.suppressed;
+            limit.interval_start = now;
+            limit.count = 0;
+            limit.suppressed = 0;
+        }
+
+        let allowed = limit.count < limit.max_per_interval;
+        if allowed {
+            limit.count += 1;
+        } else {
+            limit.suppressed = limit.suppressed.saturating_add(1);
+        }
+        self.rate_limit.set(Some(limit));
+        (allowed, report)
+    }
+
+    /// Write a record to the output, or to the deferred mode buffer
+    fn write(&self, level: log::Level, args: &fmt::Arguments) {
+        if let Some(mut ptr) = self.writer.get() {
+            self.capture(level, args);
+            if let Some(mut sink) = self.sink.get() {
+                let mut sink = unsafe { sink.as_mut() };
+                let _ = DecoratedLog::write(&mut sink, level, self.indent.get(), args);
+            }
+
+            if self.deferred.get() {
+                if self.defer(level, args).is_ok() {
+                    if let Some((bt, event)) = self.drain.get() {
+                        let _ = unsafe { bt.as_ref() }.signal_event(event);
+                    }
+                    return;
+                }
+
+                // The buffer is full, write out its contents before this record.
+                // The output cannot be used at high priority levels, the
+                // record is then dropped and reported later on.
+                if !self.try_flush_deferred() {
+                    self.lost.set(self.lost.get().saturating_add(1));
+                    return;
+                }
+            }
+
+            let writer = unsafe { ptr.as_mut() };
+            check_result(DecoratedLog::write(writer, level, self.indent.get(), args));
+        }
+    }
+
+    /// Store a record in the deferred mode buffer
+    ///
+    /// Fails if there is not enough space left in the buffer, in which case
+    /// nothing is stored.
+    fn defer(&self, level: log::Level, args: &fmt::Arguments) -> fmt::Result {
+        let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+        let buffer = unsafe { &mut *self.buffer.get() };
+        let len = buffer.len;
+        let result = DecoratedLog::write(buffer, level, self.indent.get(), args);
+        if result.is_err() {
+            buffer.len = len;
+        }
+        result
+    }
+
+    /// Append a record to the NVRAM sink's log, writing the variable out if
+    /// enough new text has accumulated
+    fn capture(&self, level: log::Level, args: &fmt::Arguments) {
+        if self.nvram.get().is_none() {
+            return;
+        }
+
+        let write_out = {
+            let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+
+            // The variable is being written from the log, leave it alone
+            if self.nvram_busy.get() {
+                return;
+            }
+
+            let log = unsafe { &mut *self.nvram_log.get() };
+            // Pieces of text which do not fit in the log at all are dropped
+            let _ = DecoratedLog::write(log, level, self.indent.get(), args);
+            log.dirty >= NVRAM_WRITE_THRESHOLD
+        };
+        if write_out {
+            self.write_nvram();
+        }
+    }
+
+    /// Write the NVRAM sink's log to its variable, if it changed
+    ///
+    /// Variables may not be written above the `CALLBACK` task priority level,
+    /// the text then waits for a later write.
+    fn write_nvram(&self) {
+        let sink = match self.nvram.get() {
+            Some(sink) => sink,
+            None => return,
+        };
+        if self.current_tpl().map_or(false, |tpl| tpl > Tpl::CALLBACK) {
+            return;
+        }
+
+        let log = {
+            let _guard = self.raise_tpl(Tpl::HIGH_LEVEL);
+            let log = unsafe { &mut *self.nvram_log.get() };
+            if self.nvram_busy.get() || log.dirty == 0 {
+                return;
+            }
+            self.nvram_busy.set(true);
+            log.dirty = 0;
+            &*log
+        };
+
+        let attributes = VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS;
+        // There is nowhere to report a failure to, and the next write will
+        // try again anyway.
+        let _ = unsafe {
+            sink.runtime_services.as_ref().set_variable(
+                sink.name,
+                &sink.vendor,
+                attributes,
+                &log.data()[..log.len],
+            )
+        };
+        self.nvram_busy.set(false);
+    }
+
+    /// Current task priority level, if it can be found out
+    ///
+    /// It is only known when a drain event was set, which also provides the
+    /// boot services.
+    fn current_tpl(&self) -> Option<Tpl> {
+        self.raise_tpl(Tpl::HIGH_LEVEL).map(|guard| guard.old_tpl())
+    }
+
+    /// Prevent the drain event from running while the buffer is modified
+    fn raise_tpl(&self, tpl: Tpl) -> Option<crate::table::boot::TplGuard> {
+        self.drain
+            .get()
+            .map(|(bt, _)| unsafe { bt.as_ref().raise_tpl(tpl) })
+    }
+}
+
+/// Allocate a zeroed buffer of `size` bytes from the pool
+fn allocate_buffer(boot_services: &BootServices, size: usize) -> crate::Result<&'static mut [u8]> {
+    let data = boot_services
+        .allocate_pool(MemoryType::LOADER_DATA, size)?
+        .log();
+    let buffer = unsafe { slice::from_raw_parts_mut(data, size) };
+    buffer.fill(0);
+    Ok(buffer.into())
+}
+
+/// Handle the result of writing to the output
+fn check_result(result: fmt::Result) {
+    // Some UEFI implementations, such as the one used by VirtualBox,
+    // may intermittently drop out some text from SimpleTextOutput and
+    // report an EFI_DEVICE_ERROR. This will be reported here as an
+    // `fmt::Error`, and given how the `log` crate is designed, our main
+    // choices when that happens are to ignore the error or panic.
+    //
+    // Ignoring errors is bad, especially when they represent loss of
+    // precious early-boot system diagnosis data, so we panic by
+    // default. But if you experience this problem and want your UEFI
+    // application to keep running when it happens, you can enable the
+    // `ignore-logger-error` cargo feature. If you do so, logging errors
+    // will be ignored by `uefi-rs` instead.
+    //
+    if !cfg!(feature = "ignore-logger-errors") {
+        result.unwrap()
     }
 }
 
 impl<'boot> log::Log for Logger {
     fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        self.writer.is_some()
+        self.writer.get().is_some()
     }
 
     fn log(&self, record: &log::Record) {
-        if let Some(mut ptr) = self.writer {
-            let writer = unsafe { ptr.as_mut() };
-            let result = DecoratedLog::write(writer, record.level(), record.args());
-
-            // Some UEFI implementations, such as the one used by VirtualBox,
-            // may intermittently drop out some text from SimpleTextOutput and
-            // report an EFI_DEVICE_ERROR. This will be reported here as an
-            // `fmt::Error`, and given how the `log` crate is designed, our main
-            // choices when that happens are to ignore the error or panic.
-            //
-            // Ignoring errors is bad, especially when they represent loss of
-            // precious early-boot system diagnosis data, so we panic by
-            // default. But if you experience this problem and want your UEFI
-            // application to keep running when it happens, you can enable the
-            // `ignore-logger-error` cargo feature. If you do so, logging errors
-            // will be ignored by `uefi-rs` instead.
-            //
-            if !cfg!(feature = "ignore-logger-errors") {
-                result.unwrap()
+        if self.writer.get().is_none() {
+            return;
+        }
+
+        let (allowed, suppressed) = self.check_rate_limit();
+        if suppressed > 0 {
+            self.write(
+                log::Level::Warn,
+                &format_args!("{} messages suppressed", suppressed),
+            );
+        }
+        if allowed {
+            let target = record.target();
+            match self.module_display.get() {
+                ModuleDisplay::Full => self.write(
+                    record.level(),
+                    &format_args!("[{}] {}", target, record.args()),
+                ),
+                ModuleDisplay::Short => {
+                    let short = target.rsplit("::").next().unwrap_or(target);
+                    self.write(
+                        record.level(),
+                        &format_args!("[{}] {}", short, record.args()),
+                    )
+                }
+                ModuleDisplay::None => self.write(record.level(), record.args()),
+            }
+
+            if record.level() == log::Level::Error {
+                if let Some(hook) = self.error_hook.get() {
+                    log::Log::flush(self);
+                    hook();
+                }
             }
         }
     }
 
     fn flush(&self) {
-        // This simple logger does not buffer output.
+        self.flush_deferred();
+        self.write_nvram();
     }
 }
 
@@ -86,7 +674,107 @@ impl<'boot> log::Log for Logger {
 unsafe impl Sync for Logger {}
 unsafe impl Send for Logger {}
 
-/// Writer wrapper which prints a log level in front of every line of text
+/// Buffer holding the records of the deferred mode
+struct DeferredBuffer {
+    /// Storage of the records, empty until the deferred mode is enabled
+    data: &'static mut [u8],
+    len: usize,
+}
+
+impl DeferredBuffer {
+    fn new() -> Self {
+        DeferredBuffer {
+            data: &mut [],
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Write for DeferredBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Strings are either stored whole or not at all, so that the buffer
+        // always contains valid UTF-8.
+        let end = self.len + s.len();
+        if end > self.data.len() {
+            return Err(fmt::Error);
+        }
+        self.data[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Most recent text of the log, as kept by the NVRAM sink
+struct NvramLog {
+    /// Storage allocated by `Logger::set_nvram_sink`, along with the boot
+    /// services it came from
+    allocated: Option<(NonNull<BootServices>, &'static mut [u8])>,
+    /// Storage used instead of `allocated`, see
+    /// `Logger::set_nvram_log_storage`
+    external: Option<&'static mut [u8]>,
+    len: usize,
+    /// Amount of text added since the variable was last written
+    dirty: usize,
+}
+
+impl NvramLog {
+    fn new() -> Self {
+        NvramLog {
+            allocated: None,
+            external: None,
+            len: 0,
+            dirty: 0,
+        }
+    }
+
+    /// Storage currently holding the text, empty if there is none
+    fn data(&self) -> &[u8] {
+        match (&self.external, &self.allocated) {
+            (Some(external), _) => external,
+            (None, Some((_, allocated))) => allocated,
+            (None, None) => &[],
+        }
+    }
+
+    /// Mutable view of the storage currently holding the text
+    fn data_mut(&mut self) -> &mut [u8] {
+        match (&mut self.external, &mut self.allocated) {
+            (Some(external), _) => external,
+            (None, Some((_, allocated))) => allocated,
+            (None, None) => &mut [],
+        }
+    }
+}
+
+impl fmt::Write for NvramLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = self.len;
+        let data = self.data_mut();
+        if s.len() > data.len() {
+            return Err(fmt::Error);
+        }
+
+        // Make room by dropping the oldest lines
+        let excess = (len + s.len()).saturating_sub(data.len());
+        if excess > 0 {
+            let cut = data[excess..len]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(len, |pos| excess + pos + 1);
+            data.copy_within(cut..len, 0);
+            len -= cut;
+        }
+
+        let end = len + s.len();
+        data[len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        self.dirty += s.len();
+        Ok(())
+    }
+}
+
+/// Writer wrapper which prints a log level and indentation in front of every
+/// line of text
 ///
 /// This is less easy than it sounds because...
 ///
@@ -100,15 +788,22 @@ unsafe impl Send for Logger {}
 struct DecoratedLog<'writer, W: fmt::Write> {
     writer: &'writer mut W,
     log_level: log::Level,
+    indent: usize,
     at_line_start: bool,
 }
 
 impl<'writer, W: fmt::Write> DecoratedLog<'writer, W> {
     // Call this method to print a level-annotated log
-    fn write(writer: &'writer mut W, log_level: log::Level, args: &fmt::Arguments) -> fmt::Result {
+    fn write(
+        writer: &'writer mut W,
+        log_level: log::Level,
+        indent: usize,
+        args: &fmt::Arguments,
+    ) -> fmt::Result {
         let mut decorated_writer = Self {
             writer,
             log_level,
+            indent,
             at_line_start: true,
         };
         writeln!(decorated_writer, "{}", *args)
@@ -125,7 +820,7 @@ impl<'writer, W: fmt::Write> fmt::Write for DecoratedLog<'writer, W> {
         // beginning of a line of output.
         let first = lines.next().unwrap_or("");
         if self.at_line_start {
-            write!(self.writer, "{}: ", self.log_level)?;
+            write!(self.writer, "{}: {:2$}", self.log_level, "", self.indent)?;
             self.at_line_start = false;
         }
         write!(self.writer, "{}", first)?;
@@ -133,7 +828,11 @@ impl<'writer, W: fmt::Write> fmt::Write for DecoratedLog<'writer, W> {
         // For the remainder of the line iterator (if any), we know that we are
         // truly at the beginning of lines of output.
         for line in lines {
-            write!(self.writer, "\n{}: {}", self.log_level, line)?;
+            write!(
+                self.writer,
+                "\n{}: {:3$}{}",
+                self.log_level, "", line, self.indent
+            )?;
         }
 
         // If the string ends with a newline character, we must 1/propagate it
@@ -299,6 +299,19 @@ impl<'boot> GraphicsOutput<'boot> {
         *self.mode.info
     }
 
+    /// Returns the index of the current mode.
+    pub fn current_mode_index(&self) -> u32 {
+        self.mode.mode
+    }
+
+    /// Returns the physical address and the size in bytes of the frame buffer.
+    ///
+    /// Unlike `frame_buffer`, this does not give access to the frame buffer.
+    /// The returned values are meaningless in `BltOnly` modes.
+    pub fn frame_buffer_region(&self) -> (u64, usize) {
+        (self.mode.fb_address, self.mode.fb_size)
+    }
+
     /// Access the frame buffer directly
     pub fn frame_buffer(&mut self) -> FrameBuffer {
         assert!(
@@ -129,6 +129,24 @@ impl<'boot> Output<'boot> {
         (self.set_cursor_position)(self, column, row).into()
     }
 
+    /// Returns the current attribute, which holds the text color in its low
+    /// nibble and the background color in the next three bits.
+    ///
+    /// The colors are numbered as in `Color`.
+    pub fn attribute(&self) -> u8 {
+        self.data.attribute as u8
+    }
+
+    /// Sets the current attribute, in the format returned by `attribute`.
+    ///
+    /// Fails with `INVALID_PARAMETER` if the top bit of `attr` is set.
+    pub fn set_attribute(&mut self, attr: u8) -> Result {
+        if attr & 0x80 != 0 {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        (self.set_attribute)(self, usize::from(attr)).into()
+    }
+
     /// Sets the text and background colors for the console.
     ///
     /// Note that for the foreground color you can choose any color.
@@ -1,6 +1,6 @@
 //! `DevicePath` protocol
 
-use crate::{proto::Protocol, unsafe_guid};
+use crate::{proto::Protocol, unsafe_guid, Char16, Result, Status};
 
 /// Device path protocol.
 ///
@@ -82,3 +82,42 @@ pub struct AcpiDevicePath {
     /// thus strings must not be used for the _UID in the ACPI name space.
     pub uid: u32,
 }
+
+/// Device path to text protocol.
+///
+/// Renders device paths in the text format described by the UEFI
+/// specification, e.g. `PciRoot(0x0)/Pci(0x1,0x1)/Ata(0x0)`.
+#[repr(C)]
+#[unsafe_guid("8b843e20-8132-4852-90cc-551a4e4a7f1c")]
+#[derive(Protocol)]
+pub struct DevicePathToText {
+    convert_device_node_to_text: usize,
+    convert_device_path_to_text: extern "efiapi" fn(
+        device_path: *const DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> *mut Char16,
+}
+
+impl DevicePathToText {
+    /// Converts a device path to text.
+    ///
+    /// `display_only` selects the shorter display representation of the
+    /// nodes, and `allow_shortcuts` enables the shortcut forms of some nodes.
+    ///
+    /// The returned string is allocated from pool memory, which the caller is
+    /// responsible for freeing.
+    pub fn convert_device_path_to_text(
+        &self,
+        device_path: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> Result<*mut Char16> {
+        let text = (self.convert_device_path_to_text)(device_path, display_only, allow_shortcuts);
+        if text.is_null() {
+            Err(Status::OUT_OF_RESOURCES.into())
+        } else {
+            Ok(text.into())
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Driver model protocols.
+
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Handle};
+
+/// The driver binding protocol, installed by drivers which follow the UEFI
+/// driver model to manage controllers.
+///
+/// When several drivers support a controller, the firmware prefers those
+/// with the highest version.
+#[repr(C)]
+#[unsafe_guid("18a031ab-b443-4d1a-a5c0-0c09261e9f71")]
+#[derive(Protocol)]
+pub struct DriverBinding {
+    supported: usize,
+    start: usize,
+    stop: usize,
+    version: u32,
+    image_handle: Handle,
+    driver_binding_handle: Handle,
+}
+
+impl DriverBinding {
+    /// Version of the driver.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Handle of the image which produced this protocol.
+    pub fn image_handle(&self) -> Handle {
+        self.image_handle
+    }
+
+    /// Handle on which this protocol is installed.
+    pub fn driver_binding_handle(&self) -> Handle {
+        self.driver_binding_handle
+    }
+}
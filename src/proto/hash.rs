@@ -0,0 +1,146 @@
+//! Hash protocols.
+//!
+//! The Hash 2 protocol gives access to the hash algorithms implemented by the
+//! firmware. Instances of it are usually created through the Hash 2 service
+//! binding protocol.
+
+#![allow(clippy::unreadable_literal)]
+
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Guid, Handle, Result, Status};
+
+/// GUID of the SHA-1 hash algorithm.
+pub const SHA1_GUID: Guid = Guid::from_values(
+    0x2ae9d80f,
+    0x3fb2,
+    0x4095,
+    0xb7b1,
+    [0xe9, 0x31, 0x57, 0xb9, 0x46, 0xb6],
+);
+
+/// GUID of the SHA-224 hash algorithm.
+pub const SHA224_GUID: Guid = Guid::from_values(
+    0x8df01a06,
+    0x9bd5,
+    0x4bf7,
+    0xb021,
+    [0xdb, 0x4f, 0xd9, 0xcc, 0xf4, 0x5b],
+);
+
+/// GUID of the SHA-256 hash algorithm.
+pub const SHA256_GUID: Guid = Guid::from_values(
+    0x51aa59de,
+    0xfdf2,
+    0x4ea3,
+    0xbc63,
+    [0x87, 0x5f, 0xb7, 0x84, 0x2e, 0xe9],
+);
+
+/// GUID of the SHA-384 hash algorithm.
+pub const SHA384_GUID: Guid = Guid::from_values(
+    0xefa96432,
+    0xde33,
+    0x4dd2,
+    0xaee6,
+    [0x32, 0x8c, 0x33, 0xdf, 0x77, 0x7a],
+);
+
+/// GUID of the SHA-512 hash algorithm.
+pub const SHA512_GUID: Guid = Guid::from_values(
+    0xcaa4381e,
+    0x750c,
+    0x4770,
+    0xb870,
+    [0x7a, 0x23, 0xb4, 0xe4, 0x21, 0x30],
+);
+
+/// Output of a hash computation.
+///
+/// This buffer is large enough for any of the supported algorithms, only the
+/// first `Hash2::hash_size` bytes of it are meaningful.
+pub type Hash2Output = [u8; 64];
+
+/// The Hash 2 protocol.
+#[repr(C)]
+#[unsafe_guid("55b1d734-c5e1-49db-9647-b16afb0e305b")]
+#[derive(Protocol)]
+pub struct Hash2 {
+    get_hash_size:
+        extern "efiapi" fn(this: &Hash2, algorithm: &Guid, hash_size: &mut usize) -> Status,
+    hash: extern "efiapi" fn(
+        this: &mut Hash2,
+        algorithm: &Guid,
+        message: *const u8,
+        message_size: usize,
+        hash: &mut Hash2Output,
+    ) -> Status,
+    hash_init: extern "efiapi" fn(this: &mut Hash2, algorithm: &Guid) -> Status,
+    hash_update:
+        extern "efiapi" fn(this: &mut Hash2, message: *const u8, message_size: usize) -> Status,
+    hash_final: extern "efiapi" fn(this: &mut Hash2, hash: &mut Hash2Output) -> Status,
+}
+
+impl Hash2 {
+    /// Returns the size of the digest produced by a hash algorithm.
+    ///
+    /// Fails with `UNSUPPORTED` if the algorithm is not implemented.
+    pub fn hash_size(&self, algorithm: &Guid) -> Result<usize> {
+        let mut size = 0;
+        (self.get_hash_size)(self, algorithm, &mut size).into_with_val(|| size)
+    }
+
+    /// Hashes a whole message in one go.
+    pub fn hash(&mut self, algorithm: &Guid, message: &[u8]) -> Result<Hash2Output> {
+        let mut output = [0; 64];
+        (self.hash)(
+            self,
+            algorithm,
+            message.as_ptr(),
+            message.len(),
+            &mut output,
+        )
+        .into_with_val(|| output)
+    }
+
+    /// Starts a multi-part hash computation.
+    ///
+    /// Any computation which was already in progress is discarded.
+    pub fn hash_init(&mut self, algorithm: &Guid) -> Result {
+        (self.hash_init)(self, algorithm).into()
+    }
+
+    /// Feeds a part of the message to the hash computation in progress.
+    pub fn hash_update(&mut self, message: &[u8]) -> Result {
+        (self.hash_update)(self, message.as_ptr(), message.len()).into()
+    }
+
+    /// Finishes the hash computation in progress and returns the digest.
+    pub fn hash_final(&mut self) -> Result<Hash2Output> {
+        let mut output = [0; 64];
+        (self.hash_final)(self, &mut output).into_with_val(|| output)
+    }
+}
+
+/// The Hash 2 service binding protocol.
+///
+/// This protocol creates and destroys handles supporting the Hash 2 protocol.
+#[repr(C)]
+#[unsafe_guid("da836f8d-217f-4ca0-99c2-1ca4e16077ea")]
+#[derive(Protocol)]
+pub struct Hash2ServiceBinding {
+    create_child: extern "efiapi" fn(this: &mut Hash2ServiceBinding, child: &mut Handle) -> Status,
+    destroy_child: extern "efiapi" fn(this: &mut Hash2ServiceBinding, child: Handle) -> Status,
+}
+
+impl Hash2ServiceBinding {
+    /// Creates a new handle supporting the Hash 2 protocol.
+    pub fn create_child(&mut self) -> Result<Handle> {
+        let mut child = unsafe { Handle::uninitialized() };
+        (self.create_child)(self, &mut child).into_with_val(|| child)
+    }
+
+    /// Destroys a handle which was created by `create_child`.
+    pub fn destroy_child(&mut self, child: Handle) -> Result {
+        (self.destroy_child)(self, child).into()
+    }
+}
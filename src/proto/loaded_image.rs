@@ -46,6 +46,16 @@ pub enum LoadOptionsError {
 }
 
 impl LoadedImage {
+    /// Returns the handle of the image which loaded this one, if any.
+    pub fn parent_handle(&self) -> Option<Handle> {
+        let null = unsafe { Handle::uninitialized() };
+        if self.parent_handle == null {
+            None
+        } else {
+            Some(self.parent_handle)
+        }
+    }
+
     /// Returns a handle to the storage device on which the image is located.
     pub fn device(&self) -> Handle {
         self.device_handle
@@ -8,3 +8,4 @@ pub mod file;
 
 pub mod block;
 pub mod fs;
+pub mod ramdisk;
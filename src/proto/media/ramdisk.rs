@@ -0,0 +1,59 @@
+//! RAM disk protocol.
+
+use crate::proto::device_path::DevicePath;
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Guid, Result, Status};
+use core::ptr;
+
+/// Disk type of a virtual disk, which is only visible to the firmware.
+#[allow(clippy::unreadable_literal)]
+pub const VIRTUAL_DISK_GUID: Guid = Guid::from_values(
+    0x77ab535a,
+    0x45fc,
+    0x624b,
+    0x5560,
+    [0xf7, 0xb2, 0x81, 0xd1, 0xf9, 0x6e],
+);
+
+/// The RAM disk protocol, used to register memory buffers as disks.
+#[repr(C)]
+#[unsafe_guid("ab38a0df-6873-44a9-87e6-d4eb56148449")]
+#[derive(Protocol)]
+pub struct RamDisk {
+    register: extern "efiapi" fn(
+        ram_disk_base: u64,
+        ram_disk_size: u64,
+        ram_disk_type: &Guid,
+        parent_device_path: *const DevicePath,
+        device_path: &mut *mut DevicePath,
+    ) -> Status,
+    unregister: extern "efiapi" fn(device_path: *const DevicePath) -> Status,
+}
+
+impl RamDisk {
+    /// Registers the `size` bytes at `base` as a RAM disk of type `disk_type`.
+    ///
+    /// On success, returns the device path of the new disk. It is allocated
+    /// from pool memory, which the caller is responsible for freeing.
+    ///
+    /// # Safety
+    ///
+    /// The memory must remain valid until the disk is unregistered.
+    pub unsafe fn register(
+        &self,
+        base: u64,
+        size: u64,
+        disk_type: &Guid,
+        parent: Option<&DevicePath>,
+    ) -> Result<*mut DevicePath> {
+        let parent = parent.map_or(ptr::null(), |p| p as *const _);
+        let mut device_path = ptr::null_mut();
+        (self.register)(base, size, disk_type, parent, &mut device_path)
+            .into_with_val(|| device_path)
+    }
+
+    /// Unregisters the RAM disk with the given device path.
+    pub fn unregister(&self, device_path: &DevicePath) -> Result {
+        (self.unregister)(device_path).into()
+    }
+}
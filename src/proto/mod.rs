@@ -29,6 +29,10 @@ pub use uefi_macros::Protocol;
 pub mod console;
 pub mod debug;
 pub mod device_path;
+pub mod driver;
+pub mod hash;
 pub mod loaded_image;
 pub mod media;
+pub mod network;
+pub mod pci;
 pub mod pi;
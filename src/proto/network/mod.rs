@@ -0,0 +1,42 @@
+//! Network access protocols.
+//!
+//! These protocols give access to the network interfaces of the system, from
+//! raw frames with the simple network protocol up to the boot services of
+//! the PXE base code protocol.
+
+pub mod pxe;
+pub mod snp;
+pub mod tcp4;
+pub mod udp4;
+
+/// A hardware address of a network interface.
+///
+/// Only the first `SimpleNetworkMode::hw_address_size` bytes of it are used,
+/// the rest is zero padding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct MacAddress(pub [u8; 32]);
+
+/// An IPv4 address, as used by the TCP4 and UDP4 protocols.
+pub type Ipv4Address = [u8; 4];
+
+/// An IPv4 or IPv6 address.
+///
+/// IPv4 addresses only use the first 4 bytes, the rest is zero padding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C, align(4))]
+pub struct IpAddress(pub [u8; 16]);
+
+impl IpAddress {
+    /// Builds an IPv4 address.
+    pub fn v4(octets: [u8; 4]) -> Self {
+        let mut addr = [0; 16];
+        addr[..4].copy_from_slice(&octets);
+        IpAddress(addr)
+    }
+
+    /// The bytes of the address, interpreted as an IPv4 address.
+    pub fn as_v4(&self) -> [u8; 4] {
+        [self.0[0], self.0[1], self.0[2], self.0[3]]
+    }
+}
@@ -0,0 +1,221 @@
+//! PXE base code protocol.
+//!
+//! This protocol implements the network services used by network boot:
+//! address configuration through DHCP, and file downloads through TFTP.
+
+use super::IpAddress;
+use crate::proto::Protocol;
+use crate::{unsafe_guid, CStr8, Result, Status};
+use core::ffi::c_void;
+use core::ptr;
+
+newtype_enum! {
+/// TFTP operation performed by the PXE base code protocol.
+pub enum TftpOpcode: u32 => {
+    /// Query the size of a file.
+    GET_FILE_SIZE = 1,
+    /// Download a file.
+    READ_FILE     = 2,
+}}
+
+/// The PXE base code protocol.
+#[repr(C)]
+#[unsafe_guid("03c4e603-ac28-11d3-9a2d-0090273fc14d")]
+#[derive(Protocol)]
+pub struct BaseCode {
+    revision: u64,
+    start: extern "efiapi" fn(this: &mut BaseCode, use_ipv6: bool) -> Status,
+    stop: extern "efiapi" fn(this: &mut BaseCode) -> Status,
+    dhcp: extern "efiapi" fn(this: &mut BaseCode, sort_offers: bool) -> Status,
+    discover: usize,
+    mtftp: extern "efiapi" fn(
+        this: &mut BaseCode,
+        operation: TftpOpcode,
+        buffer: *mut c_void,
+        overwrite: bool,
+        buffer_size: &mut u64,
+        block_size: *const usize,
+        server_ip: &IpAddress,
+        filename: *const u8,
+        info: *const c_void,
+        dont_use_buffer: bool,
+    ) -> Status,
+    udp_write: usize,
+    udp_read: usize,
+    set_ip_filter: usize,
+    arp: usize,
+    set_parameters: usize,
+    set_station_ip: usize,
+    set_packets: usize,
+    mode: *const BaseCodeMode,
+}
+
+impl BaseCode {
+    /// Enables the protocol, using IPv4 or IPv6.
+    pub fn start(&mut self, use_ipv6: bool) -> Result {
+        (self.start)(self, use_ipv6).into()
+    }
+
+    /// Disables the protocol.
+    pub fn stop(&mut self) -> Result {
+        (self.stop)(self).into()
+    }
+
+    /// Configures the station address through DHCP.
+    ///
+    /// If `sort_offers` is true, the offers of the DHCP servers are sorted
+    /// by preference, otherwise the first suitable one is used. Fails with
+    /// `TIMEOUT` if no suitable offer was received.
+    pub fn dhcp(&mut self, sort_offers: bool) -> Result {
+        (self.dhcp)(self, sort_offers).into()
+    }
+
+    /// Queries the size of a file on a TFTP server.
+    pub fn tftp_get_file_size(&mut self, server_ip: &IpAddress, filename: &CStr8) -> Result<u64> {
+        let mut buffer_size = 0;
+        (self.mtftp)(
+            self,
+            TftpOpcode::GET_FILE_SIZE,
+            ptr::null_mut(),
+            false,
+            &mut buffer_size,
+            ptr::null(),
+            server_ip,
+            filename.as_ptr() as *const u8,
+            ptr::null(),
+            false,
+        )
+        .into_with_val(|| buffer_size)
+    }
+
+    /// Downloads a file from a TFTP server, and returns its size.
+    ///
+    /// If the buffer is too small, this fails with `BUFFER_TOO_SMALL` and the
+    /// error will contain the size of the file. The download fails with
+    /// `TIMEOUT` if the server stops answering, and with `TFTP_ERROR` if it
+    /// reports an error such as a missing file.
+    pub fn tftp_read_file(
+        &mut self,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        buffer: &mut [u8],
+    ) -> Result<u64, Option<u64>> {
+        let mut buffer_size = buffer.len() as u64;
+        (self.mtftp)(
+            self,
+            TftpOpcode::READ_FILE,
+            buffer.as_mut_ptr() as *mut c_void,
+            false,
+            &mut buffer_size,
+            ptr::null(),
+            server_ip,
+            filename.as_ptr() as *const u8,
+            ptr::null(),
+            false,
+        )
+        .into_with(
+            || buffer_size,
+            |s| {
+                if s == Status::BUFFER_TOO_SMALL {
+                    Some(buffer_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Returns the current state of the protocol.
+    pub fn mode(&self) -> &BaseCodeMode {
+        unsafe { &*self.mode }
+    }
+}
+
+/// A DHCP or PXE packet received or sent by the protocol.
+#[derive(Clone, Copy)]
+#[repr(C, align(4))]
+pub struct Packet(pub [u8; 1472]);
+
+impl Packet {
+    /// The "next server" address of a DHCPv4 packet, usually that of the TFTP
+    /// server to boot from.
+    pub fn dhcpv4_server_ip(&self) -> IpAddress {
+        IpAddress::v4([self.0[20], self.0[21], self.0[22], self.0[23]])
+    }
+
+    /// The client address assigned by a DHCPv4 packet.
+    pub fn dhcpv4_your_ip(&self) -> IpAddress {
+        IpAddress::v4([self.0[16], self.0[17], self.0[18], self.0[19]])
+    }
+}
+
+/// Current state of the PXE base code protocol.
+///
+/// Only the leading fields of the firmware's structure are described here.
+#[repr(C)]
+pub struct BaseCodeMode {
+    started: bool,
+    using_ipv6: bool,
+    bis_supported: bool,
+    bis_detected: bool,
+    auto_arp: bool,
+    send_guid: bool,
+    dhcp_discover_valid: bool,
+    dhcp_ack_received: bool,
+    proxy_offer_received: bool,
+    pxe_discover_valid: bool,
+    pxe_reply_received: bool,
+    pxe_bis_reply_received: bool,
+    icmp_error_received: bool,
+    tftp_error_received: bool,
+    make_callbacks: bool,
+    ttl: u8,
+    tos: u8,
+    station_ip: IpAddress,
+    subnet_mask: IpAddress,
+    dhcp_discover: Packet,
+    dhcp_ack: Packet,
+    proxy_offer: Packet,
+}
+
+impl BaseCodeMode {
+    /// Whether the protocol has been started.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    /// Whether the protocol uses IPv6 rather than IPv4.
+    pub fn using_ipv6(&self) -> bool {
+        self.using_ipv6
+    }
+
+    /// Address of this machine.
+    pub fn station_ip(&self) -> &IpAddress {
+        &self.station_ip
+    }
+
+    /// Subnet mask of the local network.
+    pub fn subnet_mask(&self) -> &IpAddress {
+        &self.subnet_mask
+    }
+
+    /// The acknowledgement of the DHCP server, if one was received.
+    pub fn dhcp_ack(&self) -> Option<&Packet> {
+        if self.dhcp_ack_received {
+            Some(&self.dhcp_ack)
+        } else {
+            None
+        }
+    }
+
+    /// The offer of a proxy DHCP server, if one was received.
+    ///
+    /// Proxy DHCP servers only provide the boot information, not addresses.
+    pub fn proxy_offer(&self) -> Option<&Packet> {
+        if self.proxy_offer_received {
+            Some(&self.proxy_offer)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Simple network protocol.
+//!
+//! This protocol gives raw access to the frames sent and received by a
+//! network interface.
+
+use super::MacAddress;
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Event, Result, Status};
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::ptr;
+
+newtype_enum! {
+/// State of a network interface.
+pub enum NetworkState: u32 => {
+    /// The interface is stopped.
+    STOPPED     = 0,
+    /// The interface is started, but not initialized.
+    STARTED     = 1,
+    /// The interface is ready to send and receive frames.
+    INITIALIZED = 2,
+}}
+
+bitflags! {
+    /// Kinds of frames which the interface receives.
+    pub struct ReceiveFlags: u32 {
+        /// Frames sent to the interface's address.
+        const UNICAST = 0x01;
+        /// Frames sent to the enabled multicast addresses.
+        const MULTICAST = 0x02;
+        /// Frames sent to the broadcast address.
+        const BROADCAST = 0x04;
+        /// All frames, whatever their destination.
+        const PROMISCUOUS = 0x08;
+        /// Frames sent to any multicast address.
+        const PROMISCUOUS_MULTICAST = 0x10;
+    }
+}
+
+/// The simple network protocol.
+#[repr(C)]
+#[unsafe_guid("a19832b9-ac25-11d3-9a2d-0090273fc14d")]
+#[derive(Protocol)]
+pub struct SimpleNetwork {
+    revision: u64,
+    start: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    stop: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    initialize: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        extra_rx_buffer_size: usize,
+        extra_tx_buffer_size: usize,
+    ) -> Status,
+    reset: usize,
+    shutdown: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    receive_filters: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        enable: ReceiveFlags,
+        disable: ReceiveFlags,
+        reset_mcast_filter: bool,
+        mcast_filter_count: usize,
+        mcast_filter: *const MacAddress,
+    ) -> Status,
+    station_address: usize,
+    statistics: usize,
+    mcast_ip_to_mac: usize,
+    nv_data: usize,
+    get_status: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        interrupt_status: *mut u32,
+        tx_buf: *mut *mut c_void,
+    ) -> Status,
+    transmit: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        header_size: usize,
+        buffer_size: usize,
+        buffer: *const c_void,
+        src_addr: *const MacAddress,
+        dest_addr: *const MacAddress,
+        protocol: *const u16,
+    ) -> Status,
+    receive: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        header_size: *mut usize,
+        buffer_size: &mut usize,
+        buffer: *mut c_void,
+        src_addr: *mut MacAddress,
+        dest_addr: *mut MacAddress,
+        protocol: *mut u16,
+    ) -> Status,
+    wait_for_packet: Event,
+    mode: *const SimpleNetworkMode,
+}
+
+impl SimpleNetwork {
+    /// Changes the state of the interface from stopped to started.
+    pub fn start(&self) -> Result {
+        (self.start)(self).into()
+    }
+
+    /// Changes the state of the interface from started to stopped.
+    pub fn stop(&self) -> Result {
+        (self.stop)(self).into()
+    }
+
+    /// Allocates the resources of the interface and brings it from the started
+    /// to the initialized state.
+    ///
+    /// The extra buffer sizes request additional transmit and receive buffer
+    /// space from the driver, they can be zero.
+    pub fn initialize(&self, extra_rx_buffer_size: usize, extra_tx_buffer_size: usize) -> Result {
+        (self.initialize)(self, extra_rx_buffer_size, extra_tx_buffer_size).into()
+    }
+
+    /// Releases the resources of the interface and brings it back to the
+    /// started state.
+    pub fn shutdown(&self) -> Result {
+        (self.shutdown)(self).into()
+    }
+
+    /// Enables and disables the reception of some kinds of frames.
+    ///
+    /// Flags which are in neither set keep their current setting.
+    pub fn receive_filters(&self, enable: ReceiveFlags, disable: ReceiveFlags) -> Result {
+        (self.receive_filters)(self, enable, disable, false, 0, ptr::null()).into()
+    }
+
+    /// Queues a frame for transmission.
+    ///
+    /// The buffer must contain a complete frame, including its media header.
+    /// This returns as soon as the driver has accepted the frame, use
+    /// `get_recycled_transmit_buffer` to find out when it is done with it.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must stay valid until the driver has recycled it.
+    pub unsafe fn transmit(&self, buffer: &[u8]) -> Result {
+        (self.transmit)(
+            self,
+            0,
+            buffer.len(),
+            buffer.as_ptr() as *const c_void,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+        )
+        .into()
+    }
+
+    /// Returns the address of a transmit buffer the driver is done with, if
+    /// any.
+    ///
+    /// Each buffer passed to `transmit` is returned exactly once.
+    pub fn get_recycled_transmit_buffer(&self) -> Result<Option<*mut u8>> {
+        let mut tx_buf = ptr::null_mut();
+        (self.get_status)(self, ptr::null_mut(), &mut tx_buf).into_with_val(|| {
+            if tx_buf.is_null() {
+                None
+            } else {
+                Some(tx_buf as *mut u8)
+            }
+        })
+    }
+
+    /// Receives a frame, including its media header.
+    ///
+    /// Returns the size of the frame, or fails with `NOT_READY` if no frame
+    /// was received. If the buffer is too small, the error will contain the
+    /// size of the frame.
+    pub fn receive(&self, buffer: &mut [u8]) -> Result<usize, Option<usize>> {
+        let mut buffer_size = buffer.len();
+        (self.receive)(
+            self,
+            ptr::null_mut(),
+            &mut buffer_size,
+            buffer.as_mut_ptr() as *mut c_void,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+        .into_with(
+            || buffer_size,
+            |s| {
+                if s == Status::BUFFER_TOO_SMALL {
+                    Some(buffer_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Event signaled when a frame has been received.
+    pub fn wait_for_packet(&self) -> Event {
+        self.wait_for_packet
+    }
+
+    /// Returns the current state and properties of the interface.
+    pub fn mode(&self) -> &SimpleNetworkMode {
+        unsafe { &*self.mode }
+    }
+}
+
+/// State and properties of a network interface.
+#[repr(C)]
+pub struct SimpleNetworkMode {
+    state: NetworkState,
+    hw_address_size: u32,
+    media_header_size: u32,
+    max_packet_size: u32,
+    nv_ram_size: u32,
+    nv_ram_access_size: u32,
+    receive_filter_mask: ReceiveFlags,
+    receive_filter_setting: ReceiveFlags,
+    max_mcast_filter_count: u32,
+    mcast_filter_count: u32,
+    mcast_filter: [MacAddress; 16],
+    current_address: MacAddress,
+    broadcast_address: MacAddress,
+    permanent_address: MacAddress,
+    if_type: u8,
+    mac_address_changeable: bool,
+    multiple_tx_supported: bool,
+    media_present_supported: bool,
+    media_present: bool,
+}
+
+impl SimpleNetworkMode {
+    /// Current state of the interface.
+    pub fn state(&self) -> NetworkState {
+        self.state
+    }
+
+    /// Size of the interface's hardware addresses, in bytes.
+    pub fn hw_address_size(&self) -> u32 {
+        self.hw_address_size
+    }
+
+    /// Size of the media header of the frames, in bytes.
+    pub fn media_header_size(&self) -> u32 {
+        self.media_header_size
+    }
+
+    /// Maximal size of the data of a frame, excluding the media header.
+    pub fn max_packet_size(&self) -> u32 {
+        self.max_packet_size
+    }
+
+    /// Kinds of frames which the interface can be set up to receive.
+    pub fn receive_filter_mask(&self) -> ReceiveFlags {
+        self.receive_filter_mask
+    }
+
+    /// Kinds of frames which the interface currently receives.
+    pub fn receive_filter_setting(&self) -> ReceiveFlags {
+        self.receive_filter_setting
+    }
+
+    /// Current hardware address of the interface.
+    pub fn current_address(&self) -> &MacAddress {
+        &self.current_address
+    }
+
+    /// Broadcast address of the network.
+    pub fn broadcast_address(&self) -> &MacAddress {
+        &self.broadcast_address
+    }
+
+    /// Factory hardware address of the interface.
+    pub fn permanent_address(&self) -> &MacAddress {
+        &self.permanent_address
+    }
+
+    /// Type of the interface, as defined by the IANA hardware types.
+    pub fn if_type(&self) -> u8 {
+        self.if_type
+    }
+
+    /// Whether the interface is connected to a network.
+    ///
+    /// Only meaningful if `media_present_supported` is true.
+    pub fn media_present(&self) -> bool {
+        self.media_present
+    }
+
+    /// Whether the interface can detect its connection to a network.
+    pub fn media_present_supported(&self) -> bool {
+        self.media_present_supported
+    }
+}
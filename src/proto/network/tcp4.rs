@@ -0,0 +1,240 @@
+//! TCP4 protocol.
+//!
+//! This protocol manages TCP connections over IPv4. Instances of it are
+//! created through the TCP4 service binding protocol, each of them handling
+//! one connection. Its operations are asynchronous: they take a completion
+//! token, whose status is updated and whose event is signaled once the
+//! operation is over.
+
+use super::Ipv4Address;
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Event, Handle, Result, Status};
+use core::ffi::c_void;
+use core::ptr;
+
+/// The TCP4 service binding protocol.
+#[repr(C)]
+#[unsafe_guid("00720665-67eb-4a99-baf7-d3c33a1c7cd9")]
+#[derive(Protocol)]
+pub struct Tcp4ServiceBinding {
+    create_child: extern "efiapi" fn(this: &mut Tcp4ServiceBinding, child: &mut Handle) -> Status,
+    destroy_child: extern "efiapi" fn(this: &mut Tcp4ServiceBinding, child: Handle) -> Status,
+}
+
+impl Tcp4ServiceBinding {
+    /// Creates a new handle supporting the TCP4 protocol.
+    pub fn create_child(&mut self) -> Result<Handle> {
+        let mut child = unsafe { Handle::uninitialized() };
+        (self.create_child)(self, &mut child).into_with_val(|| child)
+    }
+
+    /// Destroys a handle which was created by `create_child`.
+    pub fn destroy_child(&mut self, child: Handle) -> Result {
+        (self.destroy_child)(self, child).into()
+    }
+}
+
+/// The TCP4 protocol.
+#[repr(C)]
+#[unsafe_guid("65530bc7-a359-410f-b010-5aadc7ec2b62")]
+#[derive(Protocol)]
+pub struct Tcp4 {
+    get_mode_data: usize,
+    configure: extern "efiapi" fn(this: &mut Tcp4, config: *const Tcp4ConfigData) -> Status,
+    routes: usize,
+    connect: unsafe extern "efiapi" fn(this: &mut Tcp4, token: *mut Tcp4CompletionToken) -> Status,
+    accept: usize,
+    transmit: unsafe extern "efiapi" fn(this: &mut Tcp4, token: *mut Tcp4IoToken) -> Status,
+    receive: unsafe extern "efiapi" fn(this: &mut Tcp4, token: *mut Tcp4IoToken) -> Status,
+    close: unsafe extern "efiapi" fn(this: &mut Tcp4, token: *mut Tcp4CloseToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &mut Tcp4, token: *mut Tcp4CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &mut Tcp4) -> Status,
+}
+
+impl Tcp4 {
+    /// Sets the addresses and ports of the connection, or resets the
+    /// instance if `config` is `None`, which aborts the connection.
+    ///
+    /// If the instance uses the default address and the network interface
+    /// has not been configured yet, for example because DHCP is still in
+    /// progress, this fails with `NO_MAPPING`.
+    pub fn configure(&mut self, config: Option<&Tcp4ConfigData>) -> Result {
+        let config = config.map_or(ptr::null(), |config| config as *const _);
+        (self.configure)(self, config).into()
+    }
+
+    /// Opens the configured connection, which must be an active one.
+    ///
+    /// # Safety
+    ///
+    /// The token must remain valid until the operation is over, as indicated
+    /// by its status.
+    pub unsafe fn connect(&mut self, token: &mut Tcp4CompletionToken) -> Result {
+        (self.connect)(self, token).into()
+    }
+
+    /// Queues data for transmission.
+    ///
+    /// # Safety
+    ///
+    /// The token and the transmit data it points to must remain valid until
+    /// the operation is over, as indicated by the status of the token.
+    pub unsafe fn transmit(&mut self, token: &mut Tcp4IoToken) -> Result {
+        (self.transmit)(self, token).into()
+    }
+
+    /// Asks for data to be received into the buffers of the token's receive
+    /// data, whose length is updated once the operation is over.
+    ///
+    /// # Safety
+    ///
+    /// The token and the receive data it points to must remain valid until
+    /// the operation is over, as indicated by the status of the token.
+    pub unsafe fn receive(&mut self, token: &mut Tcp4IoToken) -> Result {
+        (self.receive)(self, token).into()
+    }
+
+    /// Closes the connection, gracefully or not.
+    ///
+    /// # Safety
+    ///
+    /// The token must remain valid until the operation is over, as indicated
+    /// by its status.
+    pub unsafe fn close(&mut self, token: &mut Tcp4CloseToken) -> Result {
+        (self.close)(self, token).into()
+    }
+
+    /// Aborts a pending operation, or all of them if `token` is `None`.
+    ///
+    /// # Safety
+    ///
+    /// The token must be the completion token of one which was handed to
+    /// another method of this protocol.
+    pub unsafe fn cancel(&mut self, token: Option<&mut Tcp4CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        (self.cancel)(self, token).into()
+    }
+
+    /// Processes the pending network traffic.
+    ///
+    /// The driver also does this periodically, calling this speeds up the
+    /// completion of the pending operations.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+}
+
+/// Addresses and ports of a TCP4 connection.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct Tcp4AccessPoint {
+    /// Use the address of the network interface as the station address.
+    pub use_default_address: bool,
+    /// Station address, if `use_default_address` is false.
+    pub station_address: Ipv4Address,
+    /// Subnet mask, if `use_default_address` is false.
+    pub subnet_mask: Ipv4Address,
+    /// Local port, zero meaning a random one.
+    pub station_port: u16,
+    /// Remote address.
+    pub remote_address: Ipv4Address,
+    /// Remote port.
+    pub remote_port: u16,
+    /// Whether the connection is opened by this side rather than the remote.
+    pub active_flag: bool,
+}
+
+/// Configuration of a TCP4 instance.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Tcp4ConfigData {
+    /// Type of service field of the outgoing IPv4 headers.
+    pub type_of_service: u8,
+    /// Time to live field of the outgoing IPv4 headers.
+    pub time_to_live: u8,
+    /// Addresses and ports of the connection.
+    pub access_point: Tcp4AccessPoint,
+    /// TCP options, or null to use the default ones.
+    pub control_option: *const c_void,
+}
+
+/// Token tracking an asynchronous operation of the TCP4 protocol.
+#[repr(C)]
+pub struct Tcp4CompletionToken {
+    /// Event signaled once the operation is over.
+    pub event: Event,
+    /// Status of the operation, updated by the driver once it is over.
+    pub status: Status,
+}
+
+/// Data handed over by a transmit or receive operation.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union Tcp4Packet {
+    /// Buffers filled by `Tcp4::receive`.
+    pub rx_data: *mut Tcp4ReceiveData,
+    /// Data to be sent by `Tcp4::transmit`.
+    pub tx_data: *const Tcp4TransmitData,
+}
+
+/// Token tracking a transmit or receive operation.
+#[repr(C)]
+pub struct Tcp4IoToken {
+    /// Status of the operation.
+    pub completion_token: Tcp4CompletionToken,
+    /// Data sent or received by the operation.
+    pub packet: Tcp4Packet,
+}
+
+/// Token tracking a close operation.
+#[repr(C)]
+pub struct Tcp4CloseToken {
+    /// Status of the operation.
+    pub completion_token: Tcp4CompletionToken,
+    /// Reset the connection instead of closing it gracefully.
+    pub abort_on_close: bool,
+}
+
+/// A piece of the data of a transmit or receive operation.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Tcp4FragmentData {
+    /// Size of the piece, in bytes.
+    pub fragment_length: u32,
+    /// Address of the piece.
+    pub fragment_buffer: *mut c_void,
+}
+
+/// Buffers for received data.
+///
+/// Only a single fragment can be described with this structure.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Tcp4ReceiveData {
+    /// Whether the data is urgent, set by the driver.
+    pub urgent_flag: bool,
+    /// Total size of the buffers, updated to that of the received data.
+    pub data_length: u32,
+    /// Number of buffers.
+    pub fragment_count: u32,
+    /// Buffers to receive the data into.
+    pub fragment_table: [Tcp4FragmentData; 1],
+}
+
+/// Data to be sent.
+///
+/// Only a single fragment can be described with this structure.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Tcp4TransmitData {
+    /// Push the data to the receiving application right away.
+    pub push: bool,
+    /// The data is urgent.
+    pub urgent: bool,
+    /// Total size of the data, in bytes.
+    pub data_length: u32,
+    /// Number of pieces of the data.
+    pub fragment_count: u32,
+    /// Pieces of the data.
+    pub fragment_table: [Tcp4FragmentData; 1],
+}
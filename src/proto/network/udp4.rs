@@ -0,0 +1,227 @@
+//! UDP4 protocol.
+//!
+//! This protocol sends and receives UDP datagrams over IPv4. Instances of it
+//! are created through the UDP4 service binding protocol. Its operations are
+//! asynchronous: they take a completion token, whose status is updated and
+//! whose event is signaled once the operation is over.
+
+use super::Ipv4Address;
+use crate::proto::Protocol;
+use crate::table::runtime::Time;
+use crate::{unsafe_guid, Event, Handle, Result, Status};
+use core::ffi::c_void;
+use core::{ptr, slice};
+
+/// The UDP4 service binding protocol.
+#[repr(C)]
+#[unsafe_guid("83f01464-99bd-45e5-b383-af6305d8e9e6")]
+#[derive(Protocol)]
+pub struct Udp4ServiceBinding {
+    create_child: extern "efiapi" fn(this: &mut Udp4ServiceBinding, child: &mut Handle) -> Status,
+    destroy_child: extern "efiapi" fn(this: &mut Udp4ServiceBinding, child: Handle) -> Status,
+}
+
+impl Udp4ServiceBinding {
+    /// Creates a new handle supporting the UDP4 protocol.
+    pub fn create_child(&mut self) -> Result<Handle> {
+        let mut child = unsafe { Handle::uninitialized() };
+        (self.create_child)(self, &mut child).into_with_val(|| child)
+    }
+
+    /// Destroys a handle which was created by `create_child`.
+    pub fn destroy_child(&mut self, child: Handle) -> Result {
+        (self.destroy_child)(self, child).into()
+    }
+}
+
+/// The UDP4 protocol.
+#[repr(C)]
+#[unsafe_guid("3ad9df29-4501-478d-b1f8-7f7fe70e50f3")]
+#[derive(Protocol)]
+pub struct Udp4 {
+    get_mode_data: usize,
+    configure: extern "efiapi" fn(this: &mut Udp4, config: *const Udp4ConfigData) -> Status,
+    groups: usize,
+    routes: usize,
+    transmit: unsafe extern "efiapi" fn(this: &mut Udp4, token: *mut Udp4CompletionToken) -> Status,
+    receive: unsafe extern "efiapi" fn(this: &mut Udp4, token: *mut Udp4CompletionToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &mut Udp4, token: *mut Udp4CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &mut Udp4) -> Status,
+}
+
+impl Udp4 {
+    /// Sets the addresses and ports used by this instance, or resets it if
+    /// `config` is `None`.
+    ///
+    /// If the instance uses the default address and the network interface
+    /// has not been configured yet, for example because DHCP is still in
+    /// progress, this fails with `NO_MAPPING`.
+    pub fn configure(&mut self, config: Option<&Udp4ConfigData>) -> Result {
+        let config = config.map_or(ptr::null(), |config| config as *const _);
+        (self.configure)(self, config).into()
+    }
+
+    /// Queues a datagram for transmission.
+    ///
+    /// # Safety
+    ///
+    /// The token and the transmit data it points to must remain valid until
+    /// the operation is over, as indicated by the status of the token.
+    pub unsafe fn transmit(&mut self, token: &mut Udp4CompletionToken) -> Result {
+        (self.transmit)(self, token).into()
+    }
+
+    /// Asks for a datagram to be received.
+    ///
+    /// Once the operation is over, the receive data of the token points to
+    /// the datagram, which must be given back to the driver by signaling its
+    /// recycle event.
+    ///
+    /// # Safety
+    ///
+    /// The token must remain valid until the operation is over, as indicated
+    /// by its status.
+    pub unsafe fn receive(&mut self, token: &mut Udp4CompletionToken) -> Result {
+        (self.receive)(self, token).into()
+    }
+
+    /// Aborts a pending operation, or all of them if `token` is `None`.
+    ///
+    /// # Safety
+    ///
+    /// The token must be one which was handed to `transmit` or `receive`.
+    pub unsafe fn cancel(&mut self, token: Option<&mut Udp4CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        (self.cancel)(self, token).into()
+    }
+
+    /// Processes the pending network traffic.
+    ///
+    /// The driver also does this periodically, calling this speeds up the
+    /// completion of the pending operations.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+}
+
+/// Configuration of a UDP4 instance.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct Udp4ConfigData {
+    /// Receive the datagrams sent to the broadcast address.
+    pub accept_broadcast: bool,
+    /// Receive all datagrams, whatever their destination.
+    pub accept_promiscuous: bool,
+    /// Receive the datagrams sent to any port.
+    pub accept_any_port: bool,
+    /// Let other instances use the same station port.
+    pub allow_duplicate_port: bool,
+    /// Type of service field of the outgoing IPv4 headers.
+    pub type_of_service: u8,
+    /// Time to live field of the outgoing IPv4 headers.
+    pub time_to_live: u8,
+    /// Set the don't fragment flag of the outgoing IPv4 headers.
+    pub do_not_fragment: bool,
+    /// Receive timeout in microseconds, zero meaning none.
+    pub receive_timeout: u32,
+    /// Transmit timeout in microseconds, zero meaning none.
+    pub transmit_timeout: u32,
+    /// Use the address of the network interface as the station address.
+    pub use_default_address: bool,
+    /// Station address, if `use_default_address` is false.
+    pub station_address: Ipv4Address,
+    /// Subnet mask, if `use_default_address` is false.
+    pub subnet_mask: Ipv4Address,
+    /// Local port, zero meaning a random one.
+    pub station_port: u16,
+    /// Remote address, zero meaning that every datagram specifies its own.
+    pub remote_address: Ipv4Address,
+    /// Remote port, zero meaning that every datagram specifies its own.
+    pub remote_port: u16,
+}
+
+/// Data handed over by an asynchronous operation of the UDP4 protocol.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union Udp4Packet {
+    /// Datagram received by `Udp4::receive`, filled in by the driver.
+    pub rx_data: *mut Udp4ReceiveData,
+    /// Datagram to be sent by `Udp4::transmit`.
+    pub tx_data: *const Udp4TransmitData,
+}
+
+/// Token tracking an asynchronous operation of the UDP4 protocol.
+#[repr(C)]
+pub struct Udp4CompletionToken {
+    /// Event signaled once the operation is over.
+    pub event: Event,
+    /// Status of the operation, updated by the driver once it is over.
+    pub status: Status,
+    /// Datagram sent or received by the operation.
+    pub packet: Udp4Packet,
+}
+
+/// Addresses and ports of a datagram.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct Udp4SessionData {
+    /// Address of the sender.
+    pub source_address: Ipv4Address,
+    /// Port of the sender.
+    pub source_port: u16,
+    /// Address of the receiver.
+    pub destination_address: Ipv4Address,
+    /// Port of the receiver.
+    pub destination_port: u16,
+}
+
+/// A piece of the payload of a datagram.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Udp4FragmentData {
+    /// Size of the piece, in bytes.
+    pub fragment_length: u32,
+    /// Address of the piece.
+    pub fragment_buffer: *mut c_void,
+}
+
+/// A datagram to be sent.
+///
+/// Only a single fragment can be described with this structure.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Udp4TransmitData {
+    /// Destination of the datagram, or null to use the configured one.
+    pub udp_session_data: *const Udp4SessionData,
+    /// Gateway to send the datagram through, or null to use the default one.
+    pub gateway_address: *const Ipv4Address,
+    /// Total size of the payload, in bytes.
+    pub data_length: u32,
+    /// Number of pieces of the payload.
+    pub fragment_count: u32,
+    /// Pieces of the payload.
+    pub fragment_table: [Udp4FragmentData; 1],
+}
+
+/// A datagram which was received.
+#[repr(C)]
+pub struct Udp4ReceiveData {
+    /// Time at which the datagram was received.
+    pub time_stamp: Time,
+    /// Event to be signaled once the datagram is not used anymore.
+    pub recycle_signal: Event,
+    /// Addresses and ports of the datagram.
+    pub udp_session: Udp4SessionData,
+    /// Total size of the payload, in bytes.
+    pub data_length: u32,
+    /// Number of pieces of the payload.
+    pub fragment_count: u32,
+    fragment_table: [Udp4FragmentData; 1],
+}
+
+impl Udp4ReceiveData {
+    /// Pieces of the payload of the datagram.
+    pub fn fragments(&self) -> &[Udp4FragmentData] {
+        unsafe { slice::from_raw_parts(self.fragment_table.as_ptr(), self.fragment_count as usize) }
+    }
+}
@@ -0,0 +1,6 @@
+//! PCI bus protocols.
+//!
+//! These protocols give access to the configuration, memory and I/O spaces
+//! of the devices sitting on a PCI bus.
+
+pub mod root_bridge;
@@ -0,0 +1,229 @@
+//! PCI Root Bridge I/O protocol.
+//!
+//! A root bridge produces one or more PCI buses, and this protocol provides
+//! access to the address spaces of the devices behind it. Each root bridge
+//! belongs to a PCI segment, which is reported by `segment_number`.
+
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Handle, Result, Status};
+use core::ffi::c_void;
+use core::ops::RangeInclusive;
+use core::{mem, ptr};
+
+newtype_enum! {
+/// Width of the individual accesses performed by a read or write operation.
+pub enum IoWidth: u32 => {
+    /// 8-bit access
+    U8  = 0,
+    /// 16-bit access
+    U16 = 1,
+    /// 32-bit access
+    U32 = 2,
+    /// 64-bit access
+    U64 = 3,
+}}
+
+/// Integer types which can be transferred by a single PCI access.
+///
+/// This trait is sealed and implemented for `u8`, `u16`, `u32` and `u64`.
+pub trait PciIoUnit: Copy + Default + private::Sealed {
+    /// Width of an access of this size.
+    const WIDTH: IoWidth;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_pci_io_unit {
+    ($($ty:ty => $width:ident),*) => {
+        $(
+            impl private::Sealed for $ty {}
+            impl PciIoUnit for $ty {
+                const WIDTH: IoWidth = IoWidth::$width;
+            }
+        )*
+    };
+}
+
+impl_pci_io_unit!(u8 => U8, u16 => U16, u32 => U32, u64 => U64);
+
+/// Address of a register in the configuration space of a PCI function.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PciAddress {
+    /// Bus number.
+    pub bus: u8,
+    /// Device number, in the range `0..32`.
+    pub device: u8,
+    /// Function number, in the range `0..8`.
+    pub function: u8,
+    /// Offset of the register, in the range `0..4096`.
+    ///
+    /// Offsets above `0xFF` are only reachable on PCI Express.
+    pub register: u16,
+}
+
+impl PciAddress {
+    /// Creates a new configuration space address.
+    pub fn new(bus: u8, device: u8, function: u8, register: u16) -> Self {
+        PciAddress {
+            bus,
+            device,
+            function,
+            register,
+        }
+    }
+
+    /// Checks that all fields of the address are within their valid range.
+    pub fn is_valid(&self) -> bool {
+        self.device < 32 && self.function < 8 && self.register < 4096
+    }
+
+    /// Encodes the address in the format expected by the firmware.
+    fn to_raw(self) -> u64 {
+        let (register, extended) = if self.register > 0xFF {
+            (0, u64::from(self.register))
+        } else {
+            (u64::from(self.register), 0)
+        };
+        register
+            | u64::from(self.function) << 8
+            | u64::from(self.device) << 16
+            | u64::from(self.bus) << 24
+            | extended << 32
+    }
+}
+
+/// Read and write accessors for one of the address spaces of a root bridge.
+#[repr(C)]
+struct Access {
+    read: extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        width: IoWidth,
+        address: u64,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    write: extern "efiapi" fn(
+        this: &mut PciRootBridgeIo,
+        width: IoWidth,
+        address: u64,
+        count: usize,
+        buffer: *const c_void,
+    ) -> Status,
+}
+
+/// The PCI Root Bridge I/O protocol.
+#[repr(C)]
+#[unsafe_guid("2f707ebb-4a1a-11d4-9a38-0090273fc14d")]
+#[derive(Protocol)]
+pub struct PciRootBridgeIo {
+    parent_handle: Handle,
+    poll_mem: usize,
+    poll_io: usize,
+    mem: Access,
+    io: Access,
+    pci: Access,
+    copy_mem: usize,
+    map: usize,
+    unmap: usize,
+    allocate_buffer: usize,
+    free_buffer: usize,
+    flush: usize,
+    get_attributes: usize,
+    set_attributes: usize,
+    configuration: extern "efiapi" fn(this: &PciRootBridgeIo, resources: &mut *const u8) -> Status,
+    segment_number: u32,
+}
+
+impl PciRootBridgeIo {
+    /// Returns the handle of the PCI host bridge this root bridge belongs to.
+    pub fn parent_handle(&self) -> Handle {
+        self.parent_handle
+    }
+
+    /// Returns the PCI segment this root bridge belongs to.
+    pub fn segment_number(&self) -> u32 {
+        self.segment_number
+    }
+
+    /// Returns the range of bus numbers produced by this root bridge.
+    ///
+    /// If the firmware does not describe the bus numbers it decodes, the
+    /// whole `0..=255` range is returned.
+    pub fn bus_range(&self) -> Result<RangeInclusive<u8>> {
+        /// ACPI QWORD address space descriptor tag
+        const QWORD_DESCRIPTOR: u8 = 0x8A;
+        /// ACPI end tag descriptor
+        const END_TAG: u8 = 0x79;
+        /// Resource type of a bus number range
+        const BUS_RANGE: u8 = 2;
+        /// Size of a QWORD address space descriptor
+        const QWORD_DESCRIPTOR_SIZE: usize = 46;
+        /// Offset of the minimum address in a QWORD descriptor
+        const RANGE_MIN_OFFSET: usize = 14;
+        /// Offset of the maximum address in a QWORD descriptor
+        const RANGE_MAX_OFFSET: usize = 22;
+
+        let mut resources = ptr::null();
+        (self.configuration)(self, &mut resources).into_with_val(|| {
+            if resources.is_null() {
+                return 0..=255;
+            }
+
+            // The resources are a list of QWORD address space descriptors
+            // terminated by an end tag.
+            let mut desc = resources;
+            unsafe {
+                while *desc == QWORD_DESCRIPTOR {
+                    if *desc.add(3) == BUS_RANGE {
+                        let read = |offset| ptr::read_unaligned(desc.add(offset) as *const u64);
+                        let min = read(RANGE_MIN_OFFSET);
+                        let max = read(RANGE_MAX_OFFSET);
+                        return (min as u8)..=(max as u8);
+                    }
+                    desc = desc.add(QWORD_DESCRIPTOR_SIZE);
+                }
+                debug_assert_eq!(*desc, END_TAG);
+            }
+            0..=255
+        })
+    }
+
+    /// Reads a register from the configuration space of a PCI function.
+    ///
+    /// The register offset must be aligned to the size of `T`.
+    pub fn pci_read<T: PciIoUnit>(&self, address: PciAddress) -> Result<T> {
+        if !address.is_valid() || usize::from(address.register) % mem::size_of::<T>() != 0 {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        let mut value = T::default();
+        (self.pci.read)(
+            self,
+            T::WIDTH,
+            address.to_raw(),
+            1,
+            &mut value as *mut T as *mut c_void,
+        )
+        .into_with_val(|| value)
+    }
+
+    /// Writes a register in the configuration space of a PCI function.
+    ///
+    /// The register offset must be aligned to the size of `T`.
+    pub fn pci_write<T: PciIoUnit>(&mut self, address: PciAddress, value: T) -> Result {
+        if !address.is_valid() || usize::from(address.register) % mem::size_of::<T>() != 0 {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        (self.pci.write)(
+            self,
+            T::WIDTH,
+            address.to_raw(),
+            1,
+            &value as *const T as *const c_void,
+        )
+        .into()
+    }
+}
@@ -0,0 +1,101 @@
+//! CPU architectural protocol.
+//!
+//! This protocol is produced by the DXE core of PI-compliant firmware, and
+//! abstracts the processor-specific services it relies on, such as interrupt
+//! handling and cache management.
+
+use crate::proto::Protocol;
+use crate::{unsafe_guid, Result, Status};
+use core::ffi::c_void;
+
+/// Handler for an interrupt or processor exception.
+///
+/// The handler receives the vector of the interrupt and a pointer to the
+/// architecture-specific saved processor context.
+pub type InterruptHandler = extern "efiapi" fn(vector: isize, context: *mut c_void);
+
+/// Timer value, as returned by `CpuArch::timer_value`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TimerValue {
+    /// Current value of the timer.
+    pub value: u64,
+    /// Period of the timer, in femtoseconds.
+    pub period: u64,
+}
+
+/// The CPU architectural protocol.
+#[repr(C)]
+#[unsafe_guid("26baccb1-6f42-11d4-bce7-0080c73c8881")]
+#[derive(Protocol)]
+pub struct CpuArch {
+    flush_data_cache: usize,
+    enable_interrupt: extern "efiapi" fn(this: &CpuArch) -> Status,
+    disable_interrupt: extern "efiapi" fn(this: &CpuArch) -> Status,
+    get_interrupt_state: extern "efiapi" fn(this: &CpuArch, state: &mut bool) -> Status,
+    init: usize,
+    register_interrupt_handler: extern "efiapi" fn(
+        this: &CpuArch,
+        vector: isize,
+        handler: Option<InterruptHandler>,
+    ) -> Status,
+    get_timer_value:
+        extern "efiapi" fn(this: &CpuArch, index: u32, value: &mut u64, period: *mut u64) -> Status,
+    set_memory_attributes: usize,
+    number_of_timers: u32,
+    dma_buffer_alignment: u32,
+}
+
+impl CpuArch {
+    /// Enables interrupt processing by the processor.
+    pub fn enable_interrupt(&self) -> Result {
+        (self.enable_interrupt)(self).into()
+    }
+
+    /// Disables interrupt processing by the processor.
+    pub fn disable_interrupt(&self) -> Result {
+        (self.disable_interrupt)(self).into()
+    }
+
+    /// Returns whether interrupt processing is enabled.
+    pub fn interrupt_state(&self) -> Result<bool> {
+        let mut state = false;
+        (self.get_interrupt_state)(self, &mut state).into_with_val(|| state)
+    }
+
+    /// Registers a handler for an interrupt or processor exception vector,
+    /// or unregisters the current one if `handler` is `None`.
+    ///
+    /// This fails with `ALREADY_STARTED` when registering a handler for a
+    /// vector which already has one, and with `INVALID_PARAMETER` when
+    /// unregistering the handler of a vector which has none.
+    ///
+    /// # Safety
+    ///
+    /// The handler runs in interrupt context, where most of the UEFI services
+    /// must not be used.
+    pub unsafe fn register_interrupt_handler(
+        &self,
+        vector: isize,
+        handler: Option<InterruptHandler>,
+    ) -> Result {
+        (self.register_interrupt_handler)(self, vector, handler).into()
+    }
+
+    /// Returns the number of timers which can be read with `timer_value`.
+    pub fn number_of_timers(&self) -> u32 {
+        self.number_of_timers
+    }
+
+    /// Reads one of the processor's timers.
+    pub fn timer_value(&self, index: u32) -> Result<TimerValue> {
+        let mut value = 0;
+        let mut period = 0;
+        (self.get_timer_value)(self, index, &mut value, &mut period)
+            .into_with_val(|| TimerValue { value, period })
+    }
+
+    /// Returns the alignment required for DMA buffers, in bytes.
+    pub fn dma_buffer_alignment(&self) -> u32 {
+        self.dma_buffer_alignment
+    }
+}
@@ -3,4 +3,5 @@
 //! Contains protocols defined in UEFI's
 //! Platform Initialization (PI) Specification.
 
+pub mod cpu;
 pub mod mp;
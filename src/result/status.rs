@@ -102,6 +102,12 @@ pub enum Status: usize => {
     IP_ADDRESS_CONFLICT     = ERROR_BIT | 34,
     /// A HTTP error occurred during the network operation.
     HTTP_ERROR              = ERROR_BIT | 35,
+    /// The remote side of a TCP connection closed it.
+    CONNECTION_FIN          = ERROR_BIT | 104,
+    /// The remote side of a TCP connection reset it.
+    CONNECTION_RESET        = ERROR_BIT | 105,
+    /// The remote side refused to open a TCP connection.
+    CONNECTION_REFUSED      = ERROR_BIT | 106,
 }}
 
 impl Status {
@@ -54,9 +54,9 @@ pub struct BootServices {
         events: *mut Event,
         out_index: *mut usize,
     ) -> Status,
-    signal_event: usize,
-    close_event: usize,
-    check_event: usize,
+    signal_event: extern "efiapi" fn(event: Event) -> Status,
+    close_event: unsafe extern "efiapi" fn(event: Event) -> Status,
+    check_event: extern "efiapi" fn(event: Event) -> Status,
 
     // Protocol handlers
     install_protocol_interface: usize,
@@ -65,7 +65,11 @@ pub struct BootServices {
     handle_protocol:
         extern "efiapi" fn(handle: Handle, proto: &Guid, out_proto: &mut *mut c_void) -> Status,
     _reserved: usize,
-    register_protocol_notify: usize,
+    register_protocol_notify: extern "efiapi" fn(
+        protocol: &Guid,
+        event: Event,
+        registration: *mut ProtocolSearchKey,
+    ) -> Status,
     locate_handle: unsafe extern "efiapi" fn(
         search_ty: i32,
         proto: *const Guid,
@@ -84,7 +88,7 @@ pub struct BootServices {
     load_image: usize,
     start_image: usize,
     exit: usize,
-    unload_image: usize,
+    unload_image: extern "efiapi" fn(image_handle: Handle) -> Status,
     exit_boot_services:
         unsafe extern "efiapi" fn(image_handle: Handle, map_key: MemoryMapKey) -> Status,
 
@@ -103,7 +107,14 @@ pub struct BootServices {
     disconnect_controller: usize,
 
     // Protocol open / close services
-    open_protocol: usize,
+    open_protocol: extern "efiapi" fn(
+        handle: Handle,
+        protocol: &Guid,
+        interface: *mut *mut c_void,
+        agent_handle: Handle,
+        controller_handle: Handle,
+        attributes: u32,
+    ) -> Status,
     close_protocol: usize,
     open_protocol_information: usize,
 
@@ -119,14 +130,23 @@ pub struct BootServices {
     uninstall_multiple_protocol_interfaces: usize,
 
     // CRC services
-    calculate_crc32: usize,
+    calculate_crc32: Option<
+        unsafe extern "efiapi" fn(data: *const u8, data_size: usize, crc32: &mut u32) -> Status,
+    >,
 
     // Misc services
     copy_mem: unsafe extern "efiapi" fn(dest: *mut u8, src: *const u8, len: usize),
     set_mem: unsafe extern "efiapi" fn(buffer: *mut u8, len: usize, value: u8),
 
     // New event functions (UEFI 2.0 or newer)
-    create_event_ex: usize,
+    create_event_ex: unsafe extern "efiapi" fn(
+        ty: EventType,
+        notify_tpl: Tpl,
+        notify_func: Option<EventNotifyFn>,
+        notify_ctx: *mut c_void,
+        event_group: *const Guid,
+        event: *mut Event,
+    ) -> Status,
 }
 
 impl BootServices {
@@ -254,6 +274,41 @@ impl BootServices {
         })
     }
 
+    /// Retrieves the current memory map in the firmware's format.
+    ///
+    /// Unlike `memory_map`, this does not interpret the descriptors, but
+    /// reports the size and version of the descriptors written to the buffer.
+    /// Consumers of the raw memory map must step through it using this size,
+    /// which may be larger than `size_of::<MemoryDescriptor>()`.
+    ///
+    /// The buffer must be aligned like a `MemoryDescriptor`. If it is too
+    /// small, this fails with `BUFFER_TOO_SMALL` and the layout of the memory
+    /// map is provided in the error.
+    pub fn raw_memory_map(
+        &self,
+        buffer: &mut [u8],
+    ) -> Result<(MemoryMapKey, MemoryMapLayout), MemoryMapLayout> {
+        MemoryDescriptor::assert_aligned(buffer);
+        let mut layout = MemoryMapLayout {
+            map_size: buffer.len(),
+            desc_size: 0,
+            desc_version: 0,
+        };
+        let mut map_key = MemoryMapKey(0);
+
+        #[allow(clippy::cast_ptr_alignment)]
+        let status = unsafe {
+            (self.get_memory_map)(
+                &mut layout.map_size,
+                buffer.as_mut_ptr() as *mut MemoryDescriptor,
+                &mut map_key,
+                &mut layout.desc_size,
+                &mut layout.desc_version,
+            )
+        };
+        status.into_with(|| (map_key, layout), |_| layout)
+    }
+
     /// Allocates from a memory pool. The pointer will be 8-byte aligned.
     pub fn allocate_pool(&self, mem_ty: MemoryType, size: usize) -> Result<*mut u8> {
         let mut buffer = ptr::null_mut();
@@ -289,19 +344,7 @@ impl BootServices {
         // Prepare storage for the output Event
         let mut event = MaybeUninit::<Event>::uninit();
 
-        // Use a trampoline to handle the impedance mismatch between Rust & C
-        unsafe extern "efiapi" fn notify_trampoline(e: Event, ctx: *mut c_void) {
-            let notify_fn: fn(Event) = mem::transmute(ctx);
-            notify_fn(e); // SAFETY: Aborting panics are assumed here
-        }
-        let (notify_func, notify_ctx) = notify_fn
-            .map(|notify_fn| {
-                (
-                    Some(notify_trampoline as EventNotifyFn),
-                    notify_fn as fn(Event) as *mut c_void,
-                )
-            })
-            .unwrap_or((None, ptr::null_mut()));
+        let (notify_func, notify_ctx) = notify_args(notify_fn);
 
         // Now we're ready to call UEFI
         (self.create_event)(
@@ -314,6 +357,38 @@ impl BootServices {
         .into_with_val(|| event.assume_init())
     }
 
+    /// Creates an event in the event group identified by `event_group`.
+    ///
+    /// This works like `create_event`, except that signaling any event of the
+    /// group signals all of them. Some groups are signaled by the firmware
+    /// itself, e.g. `EVENT_GROUP_READY_TO_BOOT`. This requires UEFI 2.0.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because callbacks must handle exit from boot
+    /// services correctly.
+    pub unsafe fn create_event_ex(
+        &self,
+        event_ty: EventType,
+        notify_tpl: Tpl,
+        notify_fn: Option<fn(Event)>,
+        event_group: &Guid,
+    ) -> Result<Event> {
+        let mut event = MaybeUninit::<Event>::uninit();
+
+        let (notify_func, notify_ctx) = notify_args(notify_fn);
+
+        (self.create_event_ex)(
+            event_ty,
+            notify_tpl,
+            notify_func,
+            notify_ctx,
+            event_group,
+            event.as_mut_ptr(),
+        )
+        .into_with_val(|| event.assume_init())
+    }
+
     /// Stops execution until an event is signaled
     ///
     /// This function must be called at priority level `Tpl::APPLICATION`. If an
@@ -356,6 +431,39 @@ impl BootServices {
         )
     }
 
+    /// Places an event in the signaled state.
+    ///
+    /// If the event has a notification function of type `NOTIFY_SIGNAL`, the
+    /// function is queued at the event's notification task priority level.
+    /// It runs as soon as the current task priority level allows it, which may
+    /// be before this function returns.
+    ///
+    /// This function can be called at any task priority level.
+    pub fn signal_event(&self, event: Event) -> Result {
+        (self.signal_event)(event).into()
+    }
+
+    /// Closes an event, which must not be used anymore afterwards.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure that the event is not used elsewhere, for
+    /// example in a pending asynchronous operation or by `wait_for_event`.
+    pub unsafe fn close_event(&self, event: Event) -> Result {
+        (self.close_event)(event).into()
+    }
+
+    /// Checks whether an event is in the signaled state, without waiting.
+    ///
+    /// If it is, its signaled state is cleared. Events of the `NOTIFY_SIGNAL`
+    /// type cannot be checked, and fail with `INVALID_PARAMETER`.
+    pub fn check_event(&self, event: Event) -> Result<bool> {
+        match (self.check_event)(event) {
+            Status::NOT_READY => Ok(false.into()),
+            other => other.into_with_val(|| true),
+        }
+    }
+
     /// Sets the trigger for `EventType::TIMER` event.
     pub fn set_timer(&self, event: Event, trigger_time: TimerTrigger) -> Result {
         let (ty, time) = match trigger_time {
@@ -383,6 +491,44 @@ impl BootServices {
         })
     }
 
+    /// Checks whether `handle` supports the protocol identified by `protocol`.
+    ///
+    /// This uses the test attribute of the `OpenProtocol` boot service, so the
+    /// protocol is not actually opened and does not need to be closed. The
+    /// `agent` is the handle of the image performing the test.
+    pub fn test_protocol(&self, handle: Handle, protocol: &Guid, agent: Handle) -> Result<bool> {
+        // EFI_OPEN_PROTOCOL_TEST_PROTOCOL
+        const TEST_PROTOCOL: u32 = 0x04;
+
+        let controller = unsafe { Handle::uninitialized() };
+        match (self.open_protocol)(
+            handle,
+            protocol,
+            ptr::null_mut(),
+            agent,
+            controller,
+            TEST_PROTOCOL,
+        ) {
+            Status::UNSUPPORTED => Ok(false.into()),
+            status => status.into_with_val(|| true),
+        }
+    }
+
+    /// Registers `event` to be signaled whenever an interface is installed
+    /// for `protocol`.
+    ///
+    /// The returned key can be used with `SearchType::ByRegisterNotify` to
+    /// find the handles on which the protocol was newly installed.
+    pub fn register_protocol_notify(
+        &self,
+        protocol: &Guid,
+        event: Event,
+    ) -> Result<ProtocolSearchKey> {
+        let mut key = MaybeUninit::<ProtocolSearchKey>::uninit();
+        (self.register_protocol_notify)(protocol, event, key.as_mut_ptr())
+            .into_with_val(|| unsafe { key.assume_init() })
+    }
+
     /// Enumerates all handles installed on the system which match a certain query.
     ///
     /// You should first call this function with `None` for the output buffer,
@@ -406,6 +552,7 @@ impl BootServices {
         // Obtain the needed data from the parameters.
         let (ty, guid, key) = match search_ty {
             SearchType::AllHandles => (0, ptr::null(), ptr::null_mut()),
+            SearchType::ByRegisterNotify(key) => (1, ptr::null(), key.0),
             SearchType::ByProtocol(guid) => (2, guid as *const _, ptr::null_mut()),
         };
 
@@ -430,6 +577,15 @@ impl BootServices {
         }
     }
 
+    /// Unloads an image.
+    ///
+    /// An image which was loaded but not started is always unloaded. A started
+    /// image is only unloaded if it provides an unload function, whose status
+    /// is returned, e.g. `UNSUPPORTED` if the image does not support it.
+    pub fn unload_image(&self, image: Handle) -> Result {
+        (self.unload_image)(image).into()
+    }
+
     /// Exits the UEFI boot services
     ///
     /// This unsafe method is meant to be an implementation detail of the safe
@@ -490,7 +646,7 @@ impl BootServices {
                     d.contains(&0),
                     "Watchdog data must start with a null-terminated string"
                 );
-                (d.len(), d.as_mut_ptr())
+                (mem::size_of_val(d), d.as_mut_ptr())
             })
             .unwrap_or((0, ptr::null_mut()));
 
@@ -508,6 +664,16 @@ impl BootServices {
         })
     }
 
+    /// Computes the CRC32 of a buffer, as used by the UEFI tables and GPT.
+    ///
+    /// Some firmware do not implement this service, in which case this
+    /// function fails with `UNSUPPORTED`.
+    pub fn calculate_crc32(&self, data: &[u8]) -> Result<u32> {
+        let calculate_crc32 = self.calculate_crc32.ok_or(Status::UNSUPPORTED)?;
+        let mut crc = 0;
+        unsafe { calculate_crc32(data.as_ptr(), data.len(), &mut crc) }.into_with_val(|| crc)
+    }
+
     /// Copies memory from source to destination. The buffers can overlap.
     ///
     /// # Safety
@@ -603,6 +769,7 @@ newtype_enum! {
 ///
 /// Since feeding an unexpected variant to a Rust enum is UB, this means that
 /// this C enum must be interfaced via the newtype pattern.
+#[derive(PartialOrd, Ord)]
 pub enum Tpl: usize => {
     /// Normal task execution level.
     APPLICATION = 4,
@@ -626,6 +793,14 @@ pub struct TplGuard<'boot> {
     old_tpl: Tpl,
 }
 
+impl TplGuard<'_> {
+    /// Task priority level which was current before the guard was created,
+    /// and which will be restored when it is dropped.
+    pub fn old_tpl(&self) -> Tpl {
+        self.old_tpl
+    }
+}
+
 impl Drop for TplGuard<'_> {
     fn drop(&mut self) {
         unsafe {
@@ -652,6 +827,7 @@ newtype_enum! {
 /// in the 0x70000000..0xFFFFFFFF range. Therefore, we don't know the full set
 /// of memory types at compile time, and it is _not_ safe to model this C enum
 /// as a Rust enum.
+#[derive(PartialOrd, Ord)]
 pub enum MemoryType: u32 => {
     /// This enum variant is not used.
     RESERVED                =  0,
@@ -778,6 +954,17 @@ bitflags! {
 #[repr(C)]
 pub struct MemoryMapKey(usize);
 
+/// Layout of a raw memory map, as reported by `raw_memory_map`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryMapLayout {
+    /// Size of the memory map, in bytes.
+    pub map_size: usize,
+    /// Size of a memory descriptor, in bytes.
+    pub desc_size: usize,
+    /// Version of the memory descriptors.
+    pub desc_version: u32,
+}
+
 /// An iterator of memory descriptors
 #[derive(Debug, Clone)]
 struct MemoryMapIter<'buf> {
@@ -823,9 +1010,20 @@ pub enum SearchType<'guid> {
     /// If the protocol implements the `Protocol` interface,
     /// you can use the `from_proto` function to construct a new `SearchType`.
     ByProtocol(&'guid Guid),
-    // TODO: add ByRegisterNotify once the corresponding function is implemented.
+    /// Returns the next handle on which a protocol was installed, after its
+    /// registration with `BootServices::register_protocol_notify`.
+    ///
+    /// Each search returns at most one handle, so it should be repeated until
+    /// it fails with `NOT_FOUND`.
+    ByRegisterNotify(ProtocolSearchKey),
 }
 
+/// Key returned by `BootServices::register_protocol_notify`, to search for
+/// the handles on which the protocol was installed.
+#[derive(Debug, Copy, Clone)]
+#[repr(transparent)]
+pub struct ProtocolSearchKey(*mut c_void);
+
 impl<'guid> SearchType<'guid> {
     /// Constructs a new search type for a specified protocol.
     pub fn from_proto<P: Protocol>() -> Self {
@@ -869,6 +1067,35 @@ bitflags! {
 /// Raw event notification function
 type EventNotifyFn = unsafe extern "efiapi" fn(event: Event, context: *mut c_void);
 
+/// Turns a Rust event callback into the notification function and context
+/// expected by the firmware
+fn notify_args(notify_fn: Option<fn(Event)>) -> (Option<EventNotifyFn>, *mut c_void) {
+    // Use a trampoline to handle the impedance mismatch between Rust & C
+    unsafe extern "efiapi" fn notify_trampoline(e: Event, ctx: *mut c_void) {
+        let notify_fn: fn(Event) = mem::transmute(ctx);
+        notify_fn(e); // SAFETY: Aborting panics are assumed here
+    }
+    notify_fn
+        .map(|notify_fn| {
+            (
+                Some(notify_trampoline as EventNotifyFn),
+                notify_fn as fn(Event) as *mut c_void,
+            )
+        })
+        .unwrap_or((None, ptr::null_mut()))
+}
+
+/// Event group signaled by the boot manager right before it loads and starts
+/// a boot option.
+#[allow(clippy::unreadable_literal)]
+pub const EVENT_GROUP_READY_TO_BOOT: Guid = Guid::from_values(
+    0x7ce88fb3,
+    0x4bd7,
+    0x4679,
+    0x87a8,
+    [0xa8, 0xd8, 0xde, 0xe5, 0x0d, 0x2b],
+);
+
 /// Timer events manipulation
 pub enum TimerTrigger {
     /// Cancel event's timer
@@ -75,6 +75,18 @@ pub const PROPERTIES_TABLE_GUID: Guid = Guid::from_values(
     [0xb7, 0x47, 0x34, 0x08, 0x25, 0xe5],
 );
 
+/// GUID of the memory attributes table.
+///
+/// This table describes the memory protections applied to the runtime
+/// services code and data.
+pub const MEMORY_ATTRIBUTES_TABLE_GUID: Guid = Guid::from_values(
+    0xdcfa911d,
+    0x26eb,
+    0x469f,
+    0xa220,
+    [0x38, 0xb7, 0xdc, 0x46, 0x12, 0x20],
+);
+
 /// This table contains additional information about the UEFI implementation.
 #[repr(C)]
 pub struct PropertiesTable {
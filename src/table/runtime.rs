@@ -2,7 +2,7 @@
 
 use super::Header;
 use crate::table::boot::MemoryDescriptor;
-use crate::{Result, Status};
+use crate::{CStr16, Char16, Guid, Result, Status};
 use bitflags::bitflags;
 use core::fmt;
 use core::mem::MaybeUninit;
@@ -26,7 +26,27 @@ pub struct RuntimeServices {
         desc_version: u32,
         virtual_map: *mut MemoryDescriptor,
     ) -> Status,
-    _pad2: [usize; 5],
+    _pad2: usize,
+    get_variable: unsafe extern "efiapi" fn(
+        variable_name: *const Char16,
+        vendor_guid: *const Guid,
+        attributes: *mut VariableAttributes,
+        data_size: *mut usize,
+        data: *mut u8,
+    ) -> Status,
+    get_next_variable_name: unsafe extern "efiapi" fn(
+        variable_name_size: *mut usize,
+        variable_name: *mut u16,
+        vendor_guid: *mut Guid,
+    ) -> Status,
+    set_variable: unsafe extern "efiapi" fn(
+        variable_name: *const Char16,
+        vendor_guid: *const Guid,
+        attributes: VariableAttributes,
+        data_size: usize,
+        data: *const u8,
+    ) -> Status,
+    _pad3: usize,
     reset: unsafe extern "efiapi" fn(
         rt: ResetType,
 
@@ -82,6 +102,106 @@ impl RuntimeServices {
         (self.set_virtual_address_map)(map_size, entry_size, entry_version, map_ptr).into()
     }
 
+    /// Get the size, in bytes, of the data of a variable.
+    pub fn get_variable_size(&self, name: &CStr16, vendor: &Guid) -> Result<usize> {
+        let mut data_size = 0;
+        let status = unsafe {
+            (self.get_variable)(
+                name.as_ptr(),
+                vendor,
+                ptr::null_mut(),
+                &mut data_size,
+                ptr::null_mut(),
+            )
+        };
+
+        if status == Status::BUFFER_TOO_SMALL {
+            Status::SUCCESS.into_with_val(|| data_size)
+        } else {
+            status.into_with_val(|| data_size)
+        }
+    }
+
+    /// Get the contents and attributes of a variable.
+    ///
+    /// The data is read into `buf`, and the part of it which was filled is
+    /// returned. If the buffer is too small, the error will contain the
+    /// required size.
+    pub fn get_variable<'buf>(
+        &self,
+        name: &CStr16,
+        vendor: &Guid,
+        buf: &'buf mut [u8],
+    ) -> Result<(&'buf [u8], VariableAttributes), Option<usize>> {
+        let mut attributes = VariableAttributes::empty();
+        let mut data_size = buf.len();
+        unsafe {
+            (self.get_variable)(
+                name.as_ptr(),
+                vendor,
+                &mut attributes,
+                &mut data_size,
+                buf.as_mut_ptr(),
+            )
+        }
+        .into_with(
+            move || (&buf[..data_size], attributes),
+            |s| {
+                if s == Status::BUFFER_TOO_SMALL {
+                    Some(data_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Get the name and vendor of the variable which follows the given one.
+    ///
+    /// `name` must hold the NUL-terminated name of the previous variable, or
+    /// an empty string to start the enumeration, and `vendor` its vendor GUID.
+    /// Both are overwritten with those of the next variable. Once all variables
+    /// have been returned, this fails with `NOT_FOUND`.
+    ///
+    /// If `name` is too small to hold the next name, it is left untouched and
+    /// the error contains the required size, in UCS-2 characters.
+    pub fn get_next_variable_name(
+        &self,
+        name: &mut [u16],
+        vendor: &mut Guid,
+    ) -> Result<(), Option<usize>> {
+        let mut name_size = name.len() * core::mem::size_of::<u16>();
+        unsafe { (self.get_next_variable_name)(&mut name_size, name.as_mut_ptr(), vendor) }
+            .into_with(
+                || (),
+                |s| {
+                    if s == Status::BUFFER_TOO_SMALL {
+                        Some(name_size / core::mem::size_of::<u16>())
+                    } else {
+                        None
+                    }
+                },
+            )
+    }
+
+    /// Set the value of a variable.
+    ///
+    /// Setting a variable to empty data with no access attribute deletes it.
+    ///
+    /// # Safety
+    ///
+    /// Variables are used by the firmware to configure the platform, writing
+    /// incorrect values to some of them can make the system unbootable.
+    pub unsafe fn set_variable(
+        &self,
+        name: &CStr16,
+        vendor: &Guid,
+        attributes: VariableAttributes,
+        data: &[u8],
+    ) -> Result {
+        (self.set_variable)(name.as_ptr(), vendor, attributes, data.len(), data.as_ptr()).into()
+    }
+
     /// Resets the computer.
     pub fn reset(&self, rt: ResetType, status: Status, data: Option<&[u8]>) -> ! {
         let (size, data) = match data {
@@ -103,6 +223,40 @@ impl super::Table for RuntimeServices {
     const SIGNATURE: u64 = 0x5652_4553_544e_5552;
 }
 
+/// Vendor GUID of the variables defined by the UEFI specification, such as
+/// `BootOrder` or `Timeout`.
+pub const GLOBAL_VARIABLE: Guid = Guid::from_values(
+    0x8be4df61,
+    0x93ca,
+    0x11d2,
+    0xaa0d,
+    [0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c],
+);
+
+bitflags! {
+    /// Attributes of a UEFI variable.
+    pub struct VariableAttributes: u32 {
+        /// The variable is kept in non-volatile storage, across reboots.
+        const NON_VOLATILE = 0x01;
+        /// The variable can be accessed while boot services are active.
+        const BOOTSERVICE_ACCESS = 0x02;
+        /// The variable can be accessed after boot services have been exited.
+        const RUNTIME_ACCESS = 0x04;
+        /// The variable is a hardware error record.
+        const HARDWARE_ERROR_RECORD = 0x08;
+        /// Deprecated, writes to the variable require authentication.
+        const AUTHENTICATED_WRITE_ACCESS = 0x10;
+        /// Writes to the variable must be signed with a time-based
+        /// authentication descriptor.
+        const TIME_BASED_AUTHENTICATED_WRITE_ACCESS = 0x20;
+        /// New data is appended to the variable instead of replacing it.
+        const APPEND_WRITE = 0x40;
+        /// Writes to the variable require an enhanced authentication
+        /// descriptor.
+        const ENHANCED_AUTHENTICATED_ACCESS = 0x80;
+    }
+}
+
 /// The current time information
 #[derive(Copy, Clone)]
 #[repr(C)]
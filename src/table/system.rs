@@ -42,32 +42,42 @@ impl SystemTableView for Runtime {}
 /// will be provided to replace it.
 #[repr(transparent)]
 pub struct SystemTable<View: SystemTableView> {
-    table: &'static SystemTableImpl,
+    table: *mut SystemTableImpl,
     _marker: PhantomData<View>,
 }
 
 // These parts of the UEFI System Table interface will always be available
 impl<View: SystemTableView> SystemTable<View> {
+    /// Access the underlying table
+    fn table(&self) -> &SystemTableImpl {
+        unsafe { &*self.table }
+    }
+
     /// Return the firmware vendor string
     pub fn firmware_vendor(&self) -> &CStr16 {
-        unsafe { CStr16::from_ptr(self.table.fw_vendor) }
+        unsafe { CStr16::from_ptr(self.table().fw_vendor) }
     }
 
     /// Return the firmware revision
     pub fn firmware_revision(&self) -> Revision {
-        self.table.fw_revision
+        self.table().fw_revision
     }
 
     /// Returns the revision of this table, which is defined to be
     /// the revision of the UEFI specification implemented by the firmware.
     pub fn uefi_revision(&self) -> Revision {
-        self.table.header.revision
+        self.table().header.revision
+    }
+
+    /// Returns the header of this table.
+    pub fn header(&self) -> &Header {
+        &self.table().header
     }
 
     /// Returns the config table entries, a linear array of structures
     /// pointing to other system-specific tables.
     pub fn config_table(&self) -> &[cfg::ConfigTableEntry] {
-        unsafe { slice::from_raw_parts(self.table.cfg_table, self.table.nr_cfg) }
+        unsafe { slice::from_raw_parts(self.table().cfg_table, self.table().nr_cfg) }
     }
 }
 
@@ -77,29 +87,104 @@ impl<View: SystemTableView> SystemTable<View> {
 impl SystemTable<Boot> {
     /// Returns the standard input protocol.
     pub fn stdin(&self) -> &mut text::Input {
-        unsafe { &mut *self.table.stdin }
+        unsafe { &mut *self.table().stdin }
     }
 
     /// Returns the standard output protocol.
     pub fn stdout(&self) -> &mut text::Output {
-        let stdout_ptr = self.table.stdout as *const _ as *mut _;
+        let stdout_ptr = self.table().stdout as *const _ as *mut _;
         unsafe { &mut *stdout_ptr }
     }
 
     /// Returns the standard error protocol.
     pub fn stderr(&self) -> &mut text::Output {
-        let stderr_ptr = self.table.stderr as *const _ as *mut _;
+        let stderr_ptr = self.table().stderr as *const _ as *mut _;
         unsafe { &mut *stderr_ptr }
     }
 
+    /// Returns the handle of the standard input device.
+    pub fn stdin_handle(&self) -> Handle {
+        self.table().stdin_handle
+    }
+
+    /// Returns the handle of the standard output device.
+    pub fn stdout_handle(&self) -> Handle {
+        self.table().stdout_handle
+    }
+
+    /// Redirects the standard input to another device.
+    ///
+    /// The checksum of the table is updated accordingly. If it cannot be
+    /// computed, the table is left unchanged and the error is returned.
+    ///
+    /// # Safety
+    ///
+    /// `input` must be the text input protocol of `handle`, and must remain
+    /// valid until boot services are exited. References obtained from `stdin`
+    /// before this call still point to the previous device.
+    pub unsafe fn set_stdin(&self, handle: Handle, input: *mut text::Input) -> Result {
+        let table = self.table;
+        let old = ((*table).stdin_handle, (*table).stdin);
+        (*table).stdin_handle = handle;
+        (*table).stdin = input;
+        self.update_crc().map_err(|err| {
+            (*table).stdin_handle = old.0;
+            (*table).stdin = old.1;
+            err
+        })
+    }
+
+    /// Redirects the standard output to another device.
+    ///
+    /// The checksum of the table is updated accordingly. If it cannot be
+    /// computed, the table is left unchanged and the error is returned.
+    ///
+    /// # Safety
+    ///
+    /// `output` must be the text output protocol of `handle`, and must remain
+    /// valid until boot services are exited. References obtained from `stdout`
+    /// before this call still point to the previous device.
+    pub unsafe fn set_stdout(&self, handle: Handle, output: *mut text::Output<'static>) -> Result {
+        let table = self.table;
+        let old = ((*table).stdout_handle, (*table).stdout);
+        (*table).stdout_handle = handle;
+        (*table).stdout = output;
+        self.update_crc().map_err(|err| {
+            (*table).stdout_handle = old.0;
+            (*table).stdout = old.1;
+            err
+        })
+    }
+
+    /// Recompute the checksum of the table after it was modified
+    ///
+    /// The previous checksum is restored if this fails.
+    unsafe fn update_crc(&self) -> Result {
+        let table = self.table;
+        let old_crc = (*table).header.crc;
+        (*table).header.crc = 0;
+        let size = (*table).header.size as usize;
+        let bytes = slice::from_raw_parts(table as *const u8, size);
+        match self.boot_services().calculate_crc32(bytes) {
+            Ok(crc) => {
+                (*table).header.crc = crc.log();
+                Ok(().into())
+            }
+            Err(err) => {
+                (*table).header.crc = old_crc;
+                Err(err)
+            }
+        }
+    }
+
     /// Access runtime services
     pub fn runtime_services(&self) -> &RuntimeServices {
-        self.table.runtime
+        self.table().runtime
     }
 
     /// Access boot services
     pub fn boot_services(&self) -> &BootServices {
-        unsafe { &*self.table.boot }
+        unsafe { &*self.table().boot }
     }
 
     /// Exit the UEFI boot services
@@ -201,7 +286,7 @@ impl SystemTable<Runtime> {
     /// CPU configuration which may not be preserved by OS loaders. See the
     /// "Calling Conventions" chapter of the UEFI specification for details.
     pub unsafe fn runtime_services(&self) -> &RuntimeServices {
-        self.table.runtime
+        self.table().runtime
     }
 }
 
@@ -0,0 +1,98 @@
+//! ACPI table lookups.
+
+use core::ptr;
+
+use uefi::prelude::*;
+use uefi::table::cfg::{ACPI2_GUID, ACPI_GUID};
+use uefi::Result;
+
+/// Size of the header shared by all ACPI system description tables
+const SDT_HEADER_SIZE: usize = 36;
+
+/// Read a value of type `T` at `offset` bytes from `base`
+unsafe fn read<T: Copy>(base: *const u8, offset: usize) -> T {
+    ptr::read_unaligned(base.add(offset) as *const T)
+}
+
+/// Finds the ACPI table with the given signature
+///
+/// The tables are looked up through the RSDP found in the configuration
+/// table, using the XSDT if there is one and the RSDT otherwise.
+fn find_acpi_table(signature: &[u8; 4]) -> Option<*const u8> {
+    let st = unsafe { crate::system_table().as_ref() };
+    let rsdp = st
+        .config_table()
+        .iter()
+        .find(|entry| entry.guid == ACPI2_GUID)
+        .or_else(|| {
+            st.config_table()
+                .iter()
+                .find(|entry| entry.guid == ACPI_GUID)
+        })?
+        .address as *const u8;
+
+    unsafe {
+        // Root table addresses are physical, which boot services identity-map
+        let revision: u8 = read(rsdp, 15);
+        let xsdt: u64 = if revision >= 2 { read(rsdp, 24) } else { 0 };
+        let (root, entry_size) = if xsdt != 0 {
+            (xsdt as usize as *const u8, 8)
+        } else {
+            (read::<u32>(rsdp, 16) as usize as *const u8, 4)
+        };
+        if root.is_null() {
+            return None;
+        }
+
+        let length: u32 = read(root, 4);
+        let count = (length as usize).saturating_sub(SDT_HEADER_SIZE) / entry_size;
+        (0..count)
+            .map(|i| {
+                let offset = SDT_HEADER_SIZE + i * entry_size;
+                if entry_size == 8 {
+                    read::<u64>(root, offset) as usize as *const u8
+                } else {
+                    read::<u32>(root, offset) as usize as *const u8
+                }
+            })
+            .find(|&table| !table.is_null() && read::<[u8; 4]>(table, 0) == *signature)
+    }
+}
+
+/// Description of the firmware's boot logo, as returned by `boot_logo`.
+#[derive(Debug, Copy, Clone)]
+pub struct BootLogoInfo {
+    /// Physical address of the image in memory.
+    pub image_address: u64,
+    /// Format of the image, 0 meaning a bitmap (BMP) image.
+    pub image_type: u8,
+    /// Horizontal position of the upper left corner of the image on screen.
+    pub offset_x: u32,
+    /// Vertical position of the upper left corner of the image on screen.
+    pub offset_y: u32,
+    /// Whether the image is currently displayed.
+    pub displayed: bool,
+    /// Clockwise rotation of the image relative to the screen, in degrees.
+    pub orientation: u16,
+}
+
+/// Describes the boot logo displayed by the firmware.
+///
+/// This information comes from the Boot Graphics Resource Table (BGRT), and
+/// lets a bootloader display its own graphics at the same place. Fails with
+/// `NOT_FOUND` if the firmware does not provide that table.
+pub fn boot_logo() -> Result<BootLogoInfo> {
+    let bgrt = find_acpi_table(b"BGRT").ok_or(Status::NOT_FOUND)?;
+    let info = unsafe {
+        let status: u8 = read(bgrt, 38);
+        BootLogoInfo {
+            image_address: read(bgrt, 40),
+            image_type: read(bgrt, 39),
+            offset_x: read(bgrt, 48),
+            offset_y: read(bgrt, 52),
+            displayed: status & 1 != 0,
+            orientation: u16::from((status >> 1) & 0b11) * 90,
+        }
+    };
+    Ok(info.into())
+}
@@ -0,0 +1,44 @@
+//! Configuration table helpers.
+
+use uefi::table::cfg::{
+    ACPI2_GUID, ACPI_GUID, DEBUG_IMAGE_INFO_GUID, DXE_SERVICES_GUID, HAND_OFF_BLOCK_LIST_GUID,
+    LZMA_COMPRESS_GUID, MEMORY_ATTRIBUTES_TABLE_GUID, MEMORY_STATUS_CODE_RECORD_GUID,
+    MEMORY_TYPE_INFORMATION_GUID, PROPERTIES_TABLE_GUID, SMBIOS3_GUID, SMBIOS_GUID,
+    TIANO_COMPRESS_GUID,
+};
+use uefi::Guid;
+
+/// Friendly names of the well-known configuration table entries
+const KNOWN_ENTRIES: [(Guid, &str); 13] = [
+    (ACPI_GUID, "ACPI 1.0 RSDP"),
+    (ACPI2_GUID, "ACPI 2.0 RSDP"),
+    (SMBIOS_GUID, "SMBIOS"),
+    (SMBIOS3_GUID, "SMBIOS 3.0"),
+    (PROPERTIES_TABLE_GUID, "UEFI properties table"),
+    (MEMORY_ATTRIBUTES_TABLE_GUID, "Memory attributes table"),
+    (HAND_OFF_BLOCK_LIST_GUID, "HOB list"),
+    (MEMORY_TYPE_INFORMATION_GUID, "Memory type information"),
+    (MEMORY_STATUS_CODE_RECORD_GUID, "Memory status code records"),
+    (DXE_SERVICES_GUID, "DXE services table"),
+    (LZMA_COMPRESS_GUID, "LZMA compressed filesystem"),
+    (TIANO_COMPRESS_GUID, "Tiano compressed filesystem"),
+    (DEBUG_IMAGE_INFO_GUID, "Debug image info table"),
+];
+
+/// Logs the GUID and address of every configuration table entry.
+///
+/// Entries with a well-known GUID are annotated with a friendly name. ACPI
+/// tables such as the BGRT are not part of the configuration table, they are
+/// reached through the RSDP entries.
+pub fn log_config_table() {
+    let st = unsafe { crate::system_table().as_ref() };
+    let entries = st.config_table();
+    info!("Configuration table with {} entries:", entries.len());
+    for entry in entries {
+        let name = KNOWN_ENTRIES
+            .iter()
+            .find(|(guid, _)| *guid == entry.guid)
+            .map_or("unknown", |(_, name)| name);
+        info!("  {} at {:?} ({})", entry.guid, entry.address, name);
+    }
+}
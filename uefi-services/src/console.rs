@@ -0,0 +1,104 @@
+//! Console redirection and configuration.
+
+use uefi::prelude::*;
+use uefi::proto::console::text::{Input, Output};
+use uefi::Result;
+
+/// Redirects the standard input and output of the system table, and the
+/// output of the logger, to other devices.
+///
+/// Each of `in_handle` and `out_handle` is left alone if it is `None`. Fails
+/// with `INVALID_PARAMETER`, without changing anything, if a handle does not
+/// support the text input or output protocol respectively. Also fails if the
+/// checksum of the system table cannot be updated, in which case the device
+/// concerned is left alone.
+///
+/// Existing references to the previous devices, such as those returned by
+/// `SystemTable::stdout`, keep pointing at them.
+pub fn set_active_console(in_handle: Option<Handle>, out_handle: Option<Handle>) -> Result {
+    let st = unsafe { crate::system_table().as_ref() };
+    let bt = st.boot_services();
+
+    let input = match in_handle {
+        Some(handle) => {
+            let input = bt
+                .handle_protocol::<Input>(handle)
+                .map_err(|_| Status::INVALID_PARAMETER)?
+                .log();
+            Some((handle, input.get()))
+        }
+        None => None,
+    };
+    let output = match out_handle {
+        Some(handle) => {
+            let output = bt
+                .handle_protocol::<Output>(handle)
+                .map_err(|_| Status::INVALID_PARAMETER)?
+                .log();
+            Some((handle, output.get()))
+        }
+        None => None,
+    };
+
+    unsafe {
+        if let Some((handle, input)) = input {
+            st.set_stdin(handle, input)?.log();
+        }
+        if let Some((handle, output)) = output {
+            st.set_stdout(handle, output)?.log();
+            if let Some(logger) = crate::logger() {
+                logger.set_output(&mut *output);
+            }
+        }
+    }
+    Status::SUCCESS.into()
+}
+
+/// Points the logger at the current standard output of the system table.
+///
+/// This is needed after the system table's console was changed by other
+/// means than `set_active_console`, which already takes care of it. Fails
+/// with `NOT_STARTED` if the logger is not available.
+pub fn refresh_logger_console() -> Result {
+    let st = unsafe { crate::system_table().as_ref() };
+    let logger = crate::logger().ok_or(Status::NOT_STARTED)?;
+    unsafe { logger.set_output(st.stdout()) };
+    Status::SUCCESS.into()
+}
+
+/// Switches the standard output to its text mode with the most cells.
+///
+/// Modes which the firmware reports as unavailable are skipped. Returns the
+/// number of columns and rows of the selected mode, or fails with
+/// `UNSUPPORTED` if the device has no usable mode.
+pub fn set_largest_text_mode() -> Result<(usize, usize)> {
+    let stdout = unsafe { crate::system_table().as_ref().stdout() };
+    let mode = stdout
+        .modes()
+        .map(|mode| mode.log())
+        .max_by_key(|mode| mode.columns() * mode.rows())
+        .ok_or(Status::UNSUPPORTED)?;
+
+    stdout
+        .set_mode(mode)
+        .map_inner(|()| (mode.columns(), mode.rows()))
+}
+
+/// Reads the attribute of the standard output.
+///
+/// The low nibble of the attribute is the text color, and the next three
+/// bits the background color, both numbered as in `Color`. Saving it lets
+/// colors be restored with `set_console_attribute` after changing them.
+pub fn console_attribute() -> Result<u8> {
+    let stdout = unsafe { crate::system_table().as_ref().stdout() };
+    Ok(stdout.attribute().into())
+}
+
+/// Sets the attribute of the standard output, in the format returned by
+/// `console_attribute`.
+///
+/// Fails with `INVALID_PARAMETER` if the top bit of `attr` is set.
+pub fn set_console_attribute(attr: u8) -> Result {
+    let stdout = unsafe { crate::system_table().as_ref().stdout() };
+    stdout.set_attribute(attr)
+}
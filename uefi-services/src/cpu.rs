@@ -0,0 +1,131 @@
+//! Processor exception handling and identification.
+
+use uefi::prelude::*;
+use uefi::proto::pi::cpu::{CpuArch, InterruptHandler};
+use uefi::Result;
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "aarch64")] {
+        /// Highest vector: synchronous exception, IRQ, FIQ, SError
+        const MAX_VECTOR: usize = 3;
+    } else {
+        /// Highest vector of the interrupt descriptor table
+        const MAX_VECTOR: usize = 255;
+    }
+}
+
+/// Processor architecture, as reported by `current_arch`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Arch {
+    /// 64-bit x86.
+    X86_64,
+    /// 64-bit ARM.
+    Aarch64,
+    /// 32-bit x86.
+    Ia32,
+    /// Any other architecture.
+    Other,
+}
+
+/// Returns the architecture this library was compiled for.
+///
+/// This is the architecture of the firmware as well, since UEFI images must
+/// match it, which makes it suitable for the boot information handed to a
+/// kernel.
+pub const fn current_arch() -> Arch {
+    if cfg!(target_arch = "x86_64") {
+        Arch::X86_64
+    } else if cfg!(target_arch = "aarch64") {
+        Arch::Aarch64
+    } else if cfg!(target_arch = "x86") {
+        Arch::Ia32
+    } else {
+        Arch::Other
+    }
+}
+
+/// Registers a handler for a processor exception or interrupt vector.
+///
+/// This goes through the CPU architectural protocol, and fails with
+/// `UNSUPPORTED` if the firmware does not expose it. Vectors outside of the
+/// architecture's range are rejected with `INVALID_PARAMETER`, and vectors
+/// which already have a handler with `ALREADY_STARTED`.
+///
+/// # Safety
+///
+/// The handler runs in interrupt context, where most of the UEFI services
+/// must not be used.
+pub unsafe fn register_exception_handler(vector: usize, handler: InterruptHandler) -> Result {
+    if vector > MAX_VECTOR {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let bt = crate::system_table().as_ref().boot_services();
+    let cpu = bt
+        .locate_protocol::<CpuArch>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log();
+    (*cpu.get()).register_interrupt_handler(vector as isize, Some(handler))
+}
+
+/// Checks whether the application runs under a hypervisor.
+///
+/// On x86_64 this reads the hypervisor present bit of CPUID, which physical
+/// processors always clear. Other architectures have no such simple test, so
+/// this always returns `false` there.
+pub fn is_virtualized() -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            let features = unsafe { core::arch::x86_64::__cpuid(1) };
+            features.ecx & (1 << 31) != 0
+        } else {
+            false
+        }
+    }
+}
+
+/// Returns the number of physical address bits supported by the processor.
+///
+/// On x86_64 this reads CPUID leaf 0x80000008, and assumes 36 bits, the
+/// width of PAE, on processors which lack it. Other architectures report the
+/// conservative 32 bits, which every processor supports.
+pub fn phys_address_bits() -> u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            use core::arch::x86_64::__cpuid;
+
+            let max_extended_leaf = unsafe { __cpuid(0x8000_0000) }.eax;
+            if max_extended_leaf >= 0x8000_0008 {
+                unsafe { __cpuid(0x8000_0008) }.eax as u8
+            } else {
+                36
+            }
+        } else {
+            32
+        }
+    }
+}
+
+/// Queries the vendor signature of the hypervisor, such as `b"KVMKVMKVM\0\0\0"`
+/// or `b"TCGTCGTCGTCG"` for QEMU's emulator.
+///
+/// Returns `None` when `is_virtualized` is `false`.
+pub fn hypervisor_vendor() -> Option<[u8; 12]> {
+    if !is_virtualized() {
+        return None;
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            let leaf = unsafe { core::arch::x86_64::__cpuid(0x4000_0000) };
+            let mut vendor = [0; 12];
+            vendor[0..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+            vendor[4..8].copy_from_slice(&leaf.ecx.to_le_bytes());
+            vendor[8..12].copy_from_slice(&leaf.edx.to_le_bytes());
+            Some(vendor)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! CRC32 computations and UEFI table integrity checks.
+
+use alloc::vec::Vec;
+use core::slice;
+
+use uefi::Result;
+
+/// Offset of the CRC32 field in the header of the UEFI tables
+const HEADER_CRC_OFFSET: usize = 16;
+
+/// Computes the CRC32 of `data`, in software
+///
+/// This uses the same polynomial as the firmware's `CalculateCrc32` service.
+fn crc32_builtin(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Computes the CRC32 of `data` using the firmware's `CalculateCrc32`
+/// boot service.
+///
+/// This is the checksum used by the UEFI tables and by GPT. It fails with
+/// `UNSUPPORTED` if the firmware does not implement the service, and can only
+/// be used before boot services are exited.
+pub fn crc32(data: &[u8]) -> Result<u32> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    bt.calculate_crc32(data)
+}
+
+/// Computes the CRC32 of `data` with the firmware's implementation if there
+/// is one, and in software otherwise
+pub(crate) fn crc32_any(data: &[u8]) -> u32 {
+    match crc32(data) {
+        Ok(crc) => crc.log(),
+        Err(_) => crc32_builtin(data),
+    }
+}
+
+/// Checks the integrity of the UEFI system table.
+///
+/// This recomputes the CRC32 of the table, the way the specification defines
+/// it, and compares it to the value stored in the table's header.
+pub fn verify_system_table() -> bool {
+    let st = unsafe { crate::system_table().as_ref() };
+    let header = st.header();
+
+    // The table is checksummed with its CRC32 field set to zero
+    let size = header.size as usize;
+    if size < HEADER_CRC_OFFSET + 4 {
+        return false;
+    }
+    let mut table: Vec<u8> =
+        unsafe { slice::from_raw_parts(header as *const _ as *const u8, size) }.to_vec();
+    table[HEADER_CRC_OFFSET..HEADER_CRC_OFFSET + 4].fill(0);
+
+    crc32_any(&table) == header.crc
+}
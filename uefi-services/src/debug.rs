@@ -0,0 +1,79 @@
+//! Raw debug output, independent from the logger.
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use crate::time::ticks;
+
+/// Function receiving raw debug output, if any
+static mut RAW_SINK: Option<fn(&[u8])> = None;
+
+/// Sets the function receiving raw debug output, such as phase markers.
+///
+/// Passing `None` disables raw debug output, which is the default.
+pub fn set_raw_sink(sink: Option<fn(&[u8])>) {
+    unsafe {
+        RAW_SINK = sink;
+    }
+}
+
+/// Raw sink writing to the QEMU/Bochs debug console I/O port (0xE9).
+#[cfg(target_arch = "x86_64")]
+pub fn debugcon_sink(bytes: &[u8]) {
+    for &byte in bytes {
+        unsafe {
+            asm!("out 0xe9, al", in("al") byte, options(nomem, nostack, preserves_flags));
+        }
+    }
+}
+
+/// Adapter formatting text into a raw sink
+struct RawSinkWriter(fn(&[u8]));
+
+impl fmt::Write for RawSinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        (self.0)(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Writes a boot phase marker to the raw sink.
+///
+/// The marker line is written as `[ticks] MARK label`, where `ticks` is the
+/// value of the processor's timestamp counter. This happens regardless of the
+/// log level, so that a timeline is available even when logging is off.
+/// Without a raw sink, this does nothing.
+pub fn mark(label: &str) {
+    if let Some(sink) = unsafe { RAW_SINK } {
+        let _ = writeln!(RawSinkWriter(sink), "[{}] MARK {}", ticks(), label);
+    }
+}
+
+/// Formats `data` in the classic offset/hex/ASCII layout of `hexdump -C`.
+///
+/// Each line holds 16 bytes and ends with a newline. Bytes which are not
+/// printable ASCII characters are shown as `.` in the ASCII column.
+pub fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::with_capacity((data.len() + 15) / 16 * 78);
+    for (line, chunk) in data.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", line * 16);
+        for i in 0..16 {
+            if i == 8 {
+                dump.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => {
+                    let _ = write!(dump, " {:02x}", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str("  |");
+        for &byte in chunk {
+            let printable = byte.is_ascii_graphic() || byte == b' ';
+            dump.push(if printable { byte as char } else { '.' });
+        }
+        dump.push_str("|\n");
+    }
+    dump
+}
@@ -0,0 +1,105 @@
+//! Device path helpers.
+
+use alloc::string::String;
+use core::fmt::Write;
+use core::slice;
+
+use uefi::proto::device_path::{DevicePath, DevicePathToText};
+use uefi::{CStr16, Result};
+
+use crate::fs::cstr16_to_string;
+
+/// Type of the node terminating a device path
+const END_TYPE: u8 = 0x7F;
+
+/// Subtype of the node terminating a whole device path
+const END_ENTIRE_SUBTYPE: u8 = 0xFF;
+
+/// Subtype of the node separating the instances of a device path
+const END_INSTANCE_SUBTYPE: u8 = 0x01;
+
+/// Size of the header shared by all device path nodes
+const NODE_HEADER_SIZE: usize = 4;
+
+/// Raw bytes of the device path node starting at `node`, header included
+///
+/// The node type and subtype are read as bytes, as the firmware may use
+/// values which are not covered by `DeviceType` and `DeviceSubType`.
+unsafe fn node_bytes<'a>(node: *const u8) -> &'a [u8] {
+    let len = u16::from_le_bytes([*node.add(2), *node.add(3)]) as usize;
+    slice::from_raw_parts(node, len)
+}
+
+/// Compares two device paths node by node.
+///
+/// Nodes are equal if their type, subtype, length and data are. The paths are
+/// equal if all their nodes are, up to and including the end node. Multi
+/// instance paths are compared as a whole. A malformed node, whose length is
+/// smaller than its header, makes the paths compare unequal.
+pub fn device_paths_equal(a: &DevicePath, b: &DevicePath) -> bool {
+    let mut a = a as *const DevicePath as *const u8;
+    let mut b = b as *const DevicePath as *const u8;
+    loop {
+        let (node_a, node_b) = unsafe { (node_bytes(a), node_bytes(b)) };
+        if node_a.len() < NODE_HEADER_SIZE || node_a != node_b {
+            return false;
+        }
+        if node_a[0] == END_TYPE && node_a[1] == END_ENTIRE_SUBTYPE {
+            return true;
+        }
+        unsafe {
+            a = a.add(node_a.len());
+            b = b.add(node_b.len());
+        }
+    }
+}
+
+/// Renders a device path as text.
+///
+/// The firmware's device path to text protocol is used if available. If not,
+/// each node is rendered in the generic `Path(type,subtype,data)` form of the
+/// UEFI specification, with hexadecimal values.
+pub fn device_path_to_string(dp: &DevicePath) -> Result<String> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let to_text = match bt.locate_protocol::<DevicePathToText>() {
+        Ok(to_text) => unsafe { &*to_text.log().get() },
+        Err(_) => return Ok(render_generic(dp).into()),
+    };
+    let text = to_text.convert_device_path_to_text(dp, false, false)?.log();
+    let string = cstr16_to_string(unsafe { CStr16::from_ptr(text) });
+    bt.free_pool(text as *mut u8)?.log();
+    Ok(string.into())
+}
+
+/// Renders a device path with the generic node syntax
+fn render_generic(dp: &DevicePath) -> String {
+    let mut string = String::new();
+    let mut node = dp as *const DevicePath as *const u8;
+    loop {
+        let bytes = unsafe { node_bytes(node) };
+        if bytes.len() < NODE_HEADER_SIZE {
+            string.push_str("<malformed>");
+            break;
+        }
+        match (bytes[0], bytes[1]) {
+            (END_TYPE, END_ENTIRE_SUBTYPE) => break,
+            (END_TYPE, END_INSTANCE_SUBTYPE) => string.push(','),
+            (ty, sub_type) => {
+                if !string.is_empty() && !string.ends_with(',') {
+                    string.push('/');
+                }
+                let _ = write!(string, "Path({:#x},{:#x}", ty, sub_type);
+                if bytes.len() > NODE_HEADER_SIZE {
+                    string.push(',');
+                    for byte in &bytes[NODE_HEADER_SIZE..] {
+                        let _ = write!(string, "{:02X}", byte);
+                    }
+                }
+                string.push(')');
+            }
+        }
+        node = unsafe { node.add(bytes.len()) };
+    }
+    string
+}
@@ -0,0 +1,39 @@
+//! Driver model helpers.
+
+use alloc::vec::Vec;
+
+use uefi::proto::driver::DriverBinding;
+use uefi::{Handle, Result};
+
+/// Description of a driver binding, as returned by `driver_bindings`.
+#[derive(Debug, Copy, Clone)]
+pub struct DriverBindingInfo {
+    /// Handle on which the driver binding protocol is installed.
+    pub handle: Handle,
+    /// Handle of the image of the driver.
+    pub image_handle: Handle,
+    /// Version of the driver, higher versions are preferred by the firmware.
+    pub version: u32,
+}
+
+/// Lists the drivers which follow the UEFI driver model.
+///
+/// The drivers are sorted by decreasing version, which is the order in which
+/// the firmware tries them when several support a controller.
+pub fn driver_bindings() -> Result<Vec<DriverBindingInfo>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let mut drivers = Vec::new();
+    for handle in bt.find_handles::<DriverBinding>()?.log() {
+        let binding = bt.handle_protocol::<DriverBinding>(handle)?.log();
+        let binding = unsafe { &*binding.get() };
+        drivers.push(DriverBindingInfo {
+            handle,
+            image_handle: binding.image_handle(),
+            version: binding.version(),
+        });
+    }
+    drivers.sort_by(|a, b| b.version.cmp(&a.version));
+
+    Ok(drivers.into())
+}
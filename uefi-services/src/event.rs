@@ -0,0 +1,110 @@
+//! Event helpers.
+
+use core::time::Duration;
+
+use uefi::prelude::*;
+use uefi::proto::Protocol;
+use uefi::table::boot::{
+    EventType, ProtocolSearchKey, TimerTrigger, Tpl, EVENT_GROUP_READY_TO_BOOT,
+};
+use uefi::{Event, Result};
+
+/// Waits for `event` to be signaled, for at most `timeout`.
+///
+/// Returns whether the event was signaled before the timeout expired. Like
+/// `BootServices::wait_for_event`, this must be called at the application
+/// task priority level, and the signaled state of the event is cleared.
+pub fn wait_with_timeout(event: &Event, timeout: Duration) -> Result<bool> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let timer = unsafe { bt.create_event(EventType::TIMER, Tpl::APPLICATION, None) }?.log();
+
+    // Timers count in units of 100ns
+    let ticks = (timeout.as_nanos() / 100) as u64;
+    let result = bt
+        .set_timer(timer, TimerTrigger::Relative(ticks))
+        .and_then(|_| {
+            let mut events = [*event, timer];
+            bt.wait_for_event(&mut events)
+                .map_err(|err| err.status().into())
+        })
+        .map_inner(|index| index == 0);
+
+    unsafe { bt.close_event(timer) }?.log();
+    result
+}
+
+/// Checks whether `event` is signaled, without blocking.
+///
+/// Unlike busy-waiting, this lets the firmware dispatch the notification
+/// functions of pending events, so polling loops built on it keep timers and
+/// devices running. The signaled state of the event is cleared.
+pub fn poll_event(event: &Event) -> Result<bool> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    bt.check_event(*event)
+}
+
+/// Calls `callback` whenever an instance of the protocol `P` is installed.
+///
+/// The callback runs at the `CALLBACK` task priority level. Returns the
+/// event it is attached to, and the key with which `SearchType::ByRegisterNotify`
+/// finds the handles on which `P` was installed. Closing the event stops the
+/// notifications.
+pub fn on_protocol_installed<P: Protocol>(
+    callback: fn(Event),
+) -> Result<(Event, ProtocolSearchKey)> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let event =
+        unsafe { bt.create_event(EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(callback)) }?.log();
+
+    match bt.register_protocol_notify(&P::GUID, event) {
+        Ok(key) => Ok(key.map(|key| (event, key))),
+        Err(err) => {
+            let _ = unsafe { bt.close_event(event) };
+            Err(err)
+        }
+    }
+}
+
+/// Calls `callback` when the boot manager is about to start a boot option.
+///
+/// The callback runs at the `CALLBACK` task priority level, while all boot
+/// services are still available, so unlike the callbacks registered with
+/// `on_exit_boot_services` it can allocate memory or change the graphics mode.
+/// It is not called if the application exits boot services itself, and may
+/// be called several times if booting an option fails and the boot manager
+/// tries the next one. Closing the returned event unregisters the callback.
+pub fn on_ready_to_boot(callback: fn(Event)) -> Result<Event> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    unsafe {
+        bt.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(callback),
+            &EVENT_GROUP_READY_TO_BOOT,
+        )
+    }
+}
+
+/// Number of short stalls performed by `drain_events`
+const DRAIN_STALLS: usize = 4;
+
+/// Duration of each stall of `drain_events`, in microseconds
+const DRAIN_STALL_US: usize = 1000;
+
+/// Gives pending event notifications a chance to run, e.g. right before
+/// exiting boot services.
+///
+/// Raising the task priority level to `HIGH_LEVEL` and restoring it makes the
+/// firmware dispatch the notifications which are already queued. A few short
+/// stalls then let timer ticks fire, along with the callbacks they trigger.
+/// This is best effort: events may still be signaled afterwards, e.g. by
+/// periodic timers. It must be called at the `APPLICATION` level, and takes
+/// a few milliseconds.
+pub fn drain_events() -> Result {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    drop(unsafe { bt.raise_tpl(Tpl::HIGH_LEVEL) });
+    for _ in 0..DRAIN_STALLS {
+        bt.stall(DRAIN_STALL_US);
+    }
+    Ok(().into())
+}
@@ -0,0 +1,262 @@
+//! File system helpers.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{char, mem, slice};
+
+use uefi::prelude::*;
+use uefi::proto::media::file::{
+    Directory, File, FileAttribute, FileInfo, FileMode, FileType, RegularFile,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::table::runtime::Time;
+use uefi::{CStr16, Handle, Result};
+
+use crate::image::find_image_handle;
+
+/// Opens the root directory of the volume the running image was loaded from.
+///
+/// Files can then be opened relative to it, which saves the protocol lookups
+/// when many files must be accessed.
+pub fn image_root_dir() -> Result<Directory> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let image = find_image_handle()?.log();
+    let sfs = bt.get_image_file_system(image)?.log();
+    unsafe { &mut *sfs.get() }.open_volume()
+}
+
+/// Description of a directory entry, as returned by `list_dir`.
+#[derive(Debug, Clone)]
+pub struct FileInfoEntry {
+    /// Name of the file.
+    pub name: String,
+    /// Size of the file, in bytes.
+    pub file_size: u64,
+    /// Amount of storage space used by the file, in bytes.
+    pub physical_size: u64,
+    /// Attributes of the file, telling for example if it is a directory.
+    pub attribute: FileAttribute,
+    /// Time of the last modification of the file.
+    pub modification_time: Time,
+}
+
+/// Converts an UCS-2 string to UTF-8
+pub(crate) fn cstr16_to_string(s: &CStr16) -> String {
+    char::decode_utf16(s.to_u16_slice().iter().cloned())
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Encodes a string as a NUL-terminated UCS-2 string
+///
+/// Characters outside of the basic multilingual plane cannot be represented,
+/// these strings are rejected with `INVALID_PARAMETER`, as are the ones
+/// containing a NUL character.
+pub(crate) fn ucs2_with_nul(s: &str) -> Result<Vec<u16>> {
+    let mut codes = Vec::with_capacity(s.len() + 1);
+    for c in s.chars() {
+        let code = c as u32;
+        if code == 0 || code > 0xffff {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        codes.push(code as u16);
+    }
+    codes.push(0);
+    Ok(codes.into())
+}
+
+/// Lists the entries of a directory.
+///
+/// Entries are returned in the order the firmware yields them, which includes
+/// the `.` and `..` entries of subdirectories.
+pub fn list_dir(dir: &mut Directory) -> Result<Vec<FileInfoEntry>> {
+    dir.reset_entry_readout()?.log();
+
+    // `FileInfo` needs 8-byte alignment, which a `Vec<u64>` guarantees
+    let mut storage = vec![0u64; 32];
+    let mut entries = Vec::new();
+    loop {
+        let buffer = unsafe {
+            slice::from_raw_parts_mut(
+                storage.as_mut_ptr() as *mut u8,
+                storage.len() * mem::size_of::<u64>(),
+            )
+        };
+        let info: &mut FileInfo = match dir.read_entry(buffer) {
+            Ok(completion) => match completion.log() {
+                Some(info) => info,
+                None => break,
+            },
+            Err(err) => match *err.data() {
+                Some(size) => {
+                    let len = (size + mem::size_of::<u64>() - 1) / mem::size_of::<u64>();
+                    storage.resize(len, 0);
+                    continue;
+                }
+                None => return Err(err.status().into()),
+            },
+        };
+
+        entries.push(FileInfoEntry {
+            name: cstr16_to_string(info.file_name()),
+            file_size: info.file_size(),
+            physical_size: info.physical_size(),
+            attribute: info.attribute(),
+            modification_time: *info.modification_time(),
+        });
+    }
+
+    Ok(entries.into())
+}
+
+/// Opens a regular file relative to `dir`.
+///
+/// Forward slashes in `path` are turned into the backslashes which UEFI uses
+/// as path separators. `attrs` are the attributes of the file if it gets
+/// created, which only happens with `FileMode::CreateReadWrite`. Fails with
+/// `INVALID_PARAMETER` if `path` names a directory.
+pub fn open_file(
+    dir: &mut Directory,
+    path: &str,
+    mode: FileMode,
+    attrs: FileAttribute,
+) -> Result<RegularFile> {
+    let path = path.replace('/', "\\");
+    match dir.open(&path, mode, attrs)?.log().into_type()?.log() {
+        FileType::Regular(file) => Ok(file.into()),
+        FileType::Dir(_) => Err(Status::INVALID_PARAMETER.into()),
+    }
+}
+
+/// Queries the size of a regular file, in bytes, without reading it.
+///
+/// Fails with `NOT_FOUND` if there is no file at `path`, relative to `dir`.
+pub fn file_size(dir: &mut Directory, path: &str) -> Result<u64> {
+    let mut file = open_file(dir, path, FileMode::Read, FileAttribute::empty())?.log();
+
+    // `FileInfo` needs 8-byte alignment, which a `Vec<u64>` guarantees
+    let mut storage = vec![0u64; 32];
+    loop {
+        let buffer = unsafe {
+            slice::from_raw_parts_mut(
+                storage.as_mut_ptr() as *mut u8,
+                storage.len() * mem::size_of::<u64>(),
+            )
+        };
+        match file.get_info::<FileInfo>(buffer) {
+            Ok(completion) => return Ok(completion.map(|info| info.file_size())),
+            Err(err) => match *err.data() {
+                Some(size) => {
+                    let len = (size + mem::size_of::<u64>() - 1) / mem::size_of::<u64>();
+                    storage.resize(len, 0);
+                }
+                None => return Err(err.status().into()),
+            },
+        }
+    }
+}
+
+/// Reads a file `chunk` bytes at a time, passing each piece to `f`.
+///
+/// `path` is relative to the root directory of the volume the running image
+/// was loaded from. The last piece may be shorter than `chunk`. Reading stops
+/// at the first error, including those returned by `f`. Fails with
+/// `INVALID_PARAMETER` if `chunk` is zero.
+pub fn read_file_chunked(path: &str, chunk: usize, mut f: impl FnMut(&[u8]) -> Result) -> Result {
+    if chunk == 0 {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let mut root = image_root_dir()?.log();
+    let mut file = open_file(&mut root, path, FileMode::Read, FileAttribute::empty())?.log();
+
+    let mut buffer = vec![0; chunk];
+    loop {
+        let read = file.read(&mut buffer).map_err(|err| err.status())?.log();
+        if read == 0 {
+            return Status::SUCCESS.into();
+        }
+        f(&buffer[..read])?.log();
+    }
+}
+
+/// Reads the rest of a file, from its current position
+pub(crate) fn read_to_end(file: &mut RegularFile) -> Result<Vec<u8>> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let mut data = Vec::new();
+    loop {
+        let len = data.len();
+        data.resize(len + CHUNK_SIZE, 0);
+        let read = file
+            .read(&mut data[len..])
+            .map_err(|err| err.status())?
+            .log();
+        data.truncate(len + read);
+        if read == 0 {
+            return Ok(data.into());
+        }
+    }
+}
+
+/// Looks for the regular file `filename` in `dir`, and in its subdirectories
+/// if `recursive` is set
+fn find_in_dir(dir: &mut Directory, filename: &str, recursive: bool) -> Result<Option<Vec<u8>>> {
+    if let Ok(handle) = dir.open(filename, FileMode::Read, FileAttribute::empty()) {
+        if let FileType::Regular(mut file) = handle.log().into_type()?.log() {
+            return read_to_end(&mut file).map_inner(Some);
+        }
+    }
+    if !recursive {
+        return Ok(None.into());
+    }
+
+    for entry in list_dir(dir)?.log() {
+        if !entry.attribute.contains(FileAttribute::DIRECTORY)
+            || entry.name == "."
+            || entry.name == ".."
+        {
+            continue;
+        }
+        let handle = dir
+            .open(&entry.name, FileMode::Read, FileAttribute::empty())?
+            .log();
+        if let FileType::Dir(mut subdir) = handle.into_type()?.log() {
+            if let Some(data) = find_in_dir(&mut subdir, filename, true)?.log() {
+                return Ok(Some(data).into());
+            }
+        }
+    }
+    Ok(None.into())
+}
+
+/// Looks for a file on all the volumes of the system.
+///
+/// `filename` is looked up relative to the root directory of each volume,
+/// and if `recursive` is set, relative to all of their subdirectories as
+/// well. Returns the handle of the volume and the contents of the first
+/// match, or fails with `NOT_FOUND` if no volume has the file.
+///
+/// Volumes which cannot be read are skipped.
+pub fn find_file_anywhere(filename: &str, recursive: bool) -> Result<(Handle, Vec<u8>)> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let handles = bt
+        .find_handles::<SimpleFileSystem>()
+        .map_err(|_| Status::NOT_FOUND)?
+        .log();
+
+    for handle in handles {
+        let sfs = bt.handle_protocol::<SimpleFileSystem>(handle)?.log();
+        let mut root = match unsafe { &mut *sfs.get() }.open_volume() {
+            Ok(root) => root.log(),
+            Err(_) => continue,
+        };
+        if let Ok(completion) = find_in_dir(&mut root, filename, recursive) {
+            if let Some(data) = completion.log() {
+                return Ok((handle, data).into());
+            }
+        }
+    }
+
+    Err(Status::NOT_FOUND.into())
+}
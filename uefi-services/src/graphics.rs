@@ -0,0 +1,210 @@
+//! Graphics output helpers.
+
+use uefi::prelude::*;
+use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput, PixelFormat};
+use uefi::table::boot::{AllocateType, MemoryType};
+use uefi::table::runtime::VariableAttributes;
+use uefi::{Guid, Result};
+
+use crate::memory::{memory_map, PAGE_SIZE};
+use crate::{get_variable_typed, set_variable_typed};
+
+/// Description of the current graphics mode, as returned by `current_gop_mode`.
+#[derive(Debug, Copy, Clone)]
+pub struct GopModeInfo {
+    /// Index of the mode.
+    pub index: u32,
+    /// Horizontal resolution, in pixels.
+    pub width: usize,
+    /// Vertical resolution, in pixels.
+    pub height: usize,
+    /// Number of pixels per scan line of the frame buffer.
+    pub stride: usize,
+    /// Format of the pixels of the frame buffer.
+    pub pixel_format: PixelFormat,
+    /// Physical address of the frame buffer, zero in `BltOnly` modes.
+    pub framebuffer_base: u64,
+    /// Size of the frame buffer in bytes, zero in `BltOnly` modes.
+    pub framebuffer_size: usize,
+}
+
+/// Locates the graphics output protocol
+fn gop() -> Result<&'static mut GraphicsOutput<'static>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    bt.locate_protocol::<GraphicsOutput>()
+        .map_inner(|gop| unsafe { &mut *gop.get() })
+}
+
+/// Queries the current graphics mode, without changing it.
+pub fn current_gop_mode() -> Result<GopModeInfo> {
+    let gop = gop()?.log();
+    let info = gop.current_mode_info();
+    let (width, height) = info.resolution();
+    let (framebuffer_base, framebuffer_size) = match info.pixel_format() {
+        PixelFormat::BltOnly => (0, 0),
+        _ => gop.frame_buffer_region(),
+    };
+
+    Ok(GopModeInfo {
+        index: gop.current_mode_index(),
+        width,
+        height,
+        stride: info.stride(),
+        pixel_format: info.pixel_format(),
+        framebuffer_base,
+        framebuffer_size,
+    }
+    .into())
+}
+
+/// Checks that a rectangle fits in the current graphics mode
+fn check_rect(gop: &GraphicsOutput, x: usize, y: usize, w: usize, h: usize) -> Result {
+    let (width, height) = gop.current_mode_info().resolution();
+    let fits = |start: usize, len, max| start.checked_add(len).map_or(false, |end| end <= max);
+    if fits(x, w, width) && fits(y, h, height) {
+        Status::SUCCESS.into()
+    } else {
+        Status::INVALID_PARAMETER.into()
+    }
+}
+
+/// Fills a rectangle of the screen with a color.
+///
+/// Fails with `INVALID_PARAMETER` if the rectangle does not fit in the current
+/// graphics mode.
+pub fn gop_fill_rect(x: usize, y: usize, w: usize, h: usize, color: BltPixel) -> Result {
+    let gop = gop()?.log();
+    check_rect(gop, x, y, w, h)?.log();
+    if w == 0 || h == 0 {
+        return Status::SUCCESS.into();
+    }
+
+    gop.blt(BltOp::VideoFill {
+        color,
+        dest: (x, y),
+        dims: (w, h),
+    })
+}
+
+/// Copies a buffer of `w` by `h` pixels, stored row by row, to a rectangle of
+/// the screen.
+///
+/// Fails with `INVALID_PARAMETER` if the rectangle does not fit in the current
+/// graphics mode, and with `BAD_BUFFER_SIZE` if `src` holds less than `w * h`
+/// pixels.
+pub fn gop_blt_buffer(src: &[BltPixel], x: usize, y: usize, w: usize, h: usize) -> Result {
+    let gop = gop()?.log();
+    check_rect(gop, x, y, w, h)?.log();
+    if w == 0 || h == 0 {
+        return Status::SUCCESS.into();
+    }
+    if src.len() < w * h {
+        return Status::BAD_BUFFER_SIZE.into();
+    }
+
+    gop.blt(BltOp::BufferToVideo {
+        buffer: src,
+        src: BltRegion::SubRectangle {
+            coords: (0, 0),
+            px_stride: w,
+        },
+        dest: (x, y),
+        dims: (w, h),
+    })
+}
+
+/// Keeps the frame buffer from being used as ordinary memory.
+///
+/// Frame buffers usually live in memory mapped I/O space, which the memory
+/// map does not describe as usable memory anyway. But some of them are
+/// carved out of system memory, in which case the pages they cover are
+/// allocated here as `RESERVED`, so that the memory map handed over to the
+/// kernel flags them accordingly.
+///
+/// This does nothing if there is no graphics output protocol, or if the
+/// current mode has no frame buffer.
+pub fn reserve_framebuffer() -> Result {
+    let (base, size) = match current_gop_mode() {
+        Ok(mode) => {
+            let mode = mode.log();
+            (mode.framebuffer_base, mode.framebuffer_size as u64)
+        }
+        Err(_) => return Status::SUCCESS.into(),
+    };
+    if size == 0 {
+        return Status::SUCCESS.into();
+    }
+
+    let start = base / PAGE_SIZE * PAGE_SIZE;
+    let end = (base + size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    for desc in memory_map()?.log() {
+        if desc.ty != MemoryType::CONVENTIONAL {
+            continue;
+        }
+        let desc_end = desc.phys_start + desc.page_count * PAGE_SIZE;
+        let overlap_start = start.max(desc.phys_start);
+        let overlap_end = end.min(desc_end);
+        if overlap_start < overlap_end {
+            let pages = ((overlap_end - overlap_start) / PAGE_SIZE) as usize;
+            bt.allocate_pages(
+                AllocateType::Address(overlap_start as usize),
+                MemoryType::RESERVED,
+                pages,
+            )?
+            .log();
+        }
+    }
+    Status::SUCCESS.into()
+}
+
+/// Stores the resolution of the current graphics mode in a variable.
+///
+/// The variable `var_name` of the `vendor` namespace is non-volatile, and
+/// holds the width and height as little-endian `u32`s. It can be applied on
+/// a later boot with `apply_saved_gop_mode`.
+pub fn save_gop_mode(var_name: &str, vendor: &Guid) -> Result {
+    let (width, height) = gop()?.log().current_mode_info().resolution();
+    let mut data = [0; 8];
+    data[..4].copy_from_slice(&(width as u32).to_le_bytes());
+    data[4..].copy_from_slice(&(height as u32).to_le_bytes());
+    let attributes = VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS;
+    set_variable_typed(var_name, vendor, attributes, &data)
+}
+
+/// Switches to the graphics mode closest to the resolution stored by
+/// `save_gop_mode`.
+///
+/// The closest mode is the one whose width and height differ the least from
+/// the stored ones in total. The mode is left unchanged if the variable does
+/// not exist, or if the current mode already has the closest resolution, as
+/// setting a mode clears the screen. Fails with `INVALID_PARAMETER` if the
+/// variable does not hold a resolution.
+pub fn apply_saved_gop_mode(var_name: &str, vendor: &Guid) -> Result {
+    let data = match get_variable_typed(var_name, vendor) {
+        Ok(completion) => completion.log().0,
+        Err(err) if err.status() == Status::NOT_FOUND => return Ok(().into()),
+        Err(err) => return Err(err),
+    };
+    if data.len() != 8 {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let width = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let height = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    let gop = gop()?.log();
+    let distance = |(w, h): (usize, usize)| {
+        (w as isize - width as isize).abs() + (h as isize - height as isize).abs()
+    };
+    let closest = gop
+        .modes()
+        .map(|mode| mode.log())
+        .min_by_key(|mode| distance(mode.info().resolution()))
+        .ok_or(Status::NOT_FOUND)?;
+
+    if closest.info().resolution() == gop.current_mode_info().resolution() {
+        return Ok(().into());
+    }
+    gop.set_mode(&closest)
+}
@@ -0,0 +1,17 @@
+//! Handle database helpers.
+
+use uefi::{Guid, Handle};
+
+use crate::image::find_image_handle;
+
+/// Checks whether the protocol identified by `guid` is installed on `handle`.
+///
+/// The protocol is only tested for, not opened, so this has no side effect on
+/// the drivers managing the handle. Errors, e.g. an invalid handle, are
+/// reported as the protocol not being supported.
+pub fn handle_supports(handle: Handle, guid: &Guid) -> bool {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    find_image_handle()
+        .and_then(|image| bt.test_protocol(handle, guid, image.log()))
+        .map_or(false, |supported| supported.log())
+}
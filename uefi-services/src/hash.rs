@@ -0,0 +1,45 @@
+//! Hashing through the firmware's hash implementations.
+
+use uefi::prelude::*;
+use uefi::proto::hash::{Hash2, Hash2ServiceBinding, SHA256_GUID};
+use uefi::Result;
+
+/// Length of a SHA-256 digest, in bytes
+const SHA256_SIZE: usize = 32;
+
+/// Computes the SHA-256 digest of `data` using the Hash 2 protocol.
+///
+/// Returns `UNSUPPORTED` if the firmware does not provide the protocol or the
+/// algorithm.
+pub fn sha256(data: &[u8]) -> Result<[u8; SHA256_SIZE]> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    // Use an existing instance if there is one, otherwise create our own
+    if let Ok(hash2) = bt.locate_protocol::<Hash2>() {
+        let hash2 = unsafe { &mut *hash2.log().get() };
+        return sha256_with(hash2, data);
+    }
+
+    let binding = bt
+        .locate_protocol::<Hash2ServiceBinding>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log();
+    let binding = unsafe { &mut *binding.get() };
+    let child = binding.create_child()?.log();
+    let result = bt
+        .handle_protocol::<Hash2>(child)
+        .and_then(|hash2| sha256_with(unsafe { &mut *hash2.log().get() }, data));
+    binding.destroy_child(child)?.log();
+    result
+}
+
+/// Computes a SHA-256 digest with the given Hash 2 protocol instance
+fn sha256_with(hash2: &mut Hash2, data: &[u8]) -> Result<[u8; SHA256_SIZE]> {
+    hash2.hash_init(&SHA256_GUID)?.log();
+    hash2.hash_update(data)?.log();
+    let output = hash2.hash_final()?.log();
+
+    let mut digest = [0; SHA256_SIZE];
+    digest.copy_from_slice(&output[..SHA256_SIZE]);
+    Ok(digest.into())
+}
@@ -0,0 +1,70 @@
+//! Hand-off block list helpers.
+//!
+//! On firmware following the Platform Initialization specification, the HOB
+//! list passed from the PEI phase to the DXE phase is exposed through the
+//! configuration table.
+
+use core::ptr;
+
+use uefi::newtype_enum;
+use uefi::prelude::*;
+use uefi::table::cfg::HAND_OFF_BLOCK_LIST_GUID;
+use uefi::Result;
+
+/// Type of the phase handoff information table HOB, which starts the list
+const HOB_TYPE_HANDOFF: u16 = 1;
+
+/// Offset of the boot mode in the phase handoff information table HOB
+const HANDOFF_BOOT_MODE_OFFSET: usize = 12;
+
+newtype_enum! {
+/// The boot mode of the platform, as defined by the PI specification.
+pub enum BootMode: u32 => {
+    /// Boot with full configuration.
+    FULL_CONFIGURATION                  = 0x00,
+    /// Boot with minimal configuration.
+    MINIMAL_CONFIGURATION               = 0x01,
+    /// Boot assuming that the configuration did not change.
+    ASSUMING_NO_CONFIGURATION_CHANGES   = 0x02,
+    /// Boot with full configuration, and run diagnostics.
+    FULL_CONFIGURATION_PLUS_DIAGNOSTICS = 0x03,
+    /// Boot with the default settings.
+    DEFAULT_SETTINGS                    = 0x04,
+    /// Resume from the S4 sleep state.
+    S4_RESUME                           = 0x05,
+    /// Resume from the S5 soft off state.
+    S5_RESUME                           = 0x06,
+    /// Boot with manufacturing settings.
+    MFG_MODE_SETTINGS                   = 0x07,
+    /// Resume from the S2 sleep state.
+    S2_RESUME                           = 0x10,
+    /// Resume from the S3 sleep state.
+    S3_RESUME                           = 0x11,
+    /// Boot to update the firmware.
+    FLASH_UPDATE                        = 0x12,
+    /// Boot in recovery mode.
+    RECOVERY                            = 0x20,
+}}
+
+/// Queries the boot mode of the platform.
+///
+/// This reads it from the HOB list, and fails with `UNSUPPORTED` if the
+/// firmware does not provide one.
+pub fn boot_mode() -> Result<BootMode> {
+    let st = unsafe { crate::system_table().as_ref() };
+    let hob_list = st
+        .config_table()
+        .iter()
+        .find(|entry| entry.guid == HAND_OFF_BLOCK_LIST_GUID)
+        .ok_or(Status::UNSUPPORTED)?
+        .address as *const u8;
+
+    unsafe {
+        let hob_type = ptr::read_unaligned(hob_list as *const u16);
+        if hob_type != HOB_TYPE_HANDOFF {
+            return Err(Status::UNSUPPORTED.into());
+        }
+        let mode = ptr::read_unaligned(hob_list.add(HANDOFF_BOOT_MODE_OFFSET) as *const u32);
+        Ok(BootMode(mode).into())
+    }
+}
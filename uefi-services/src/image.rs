@@ -0,0 +1,104 @@
+//! Information about the running image, and checks on other images.
+
+use core::convert::TryInto;
+
+use uefi::prelude::*;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::{Handle, Result};
+
+/// Finds the handle of the image this library is linked into.
+///
+/// This is the handle passed to `init_with_image` if any. Otherwise, this
+/// looks for the loaded image whose memory contains this function.
+pub(crate) fn find_image_handle() -> Result<Handle> {
+    if let Some(handle) = unsafe { crate::IMAGE_HANDLE } {
+        return Ok(handle.into());
+    }
+
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let address = find_image_handle as usize as u64;
+
+    for handle in bt.find_handles::<LoadedImage>()?.log() {
+        let loaded_image = bt.handle_protocol::<LoadedImage>(handle)?.log();
+        let (base, size) = unsafe { (*loaded_image.get()).info() };
+        let base = base as u64;
+        if (base..base + size).contains(&address) {
+            return Ok(handle.into());
+        }
+    }
+
+    Err(Status::NOT_FOUND.into())
+}
+
+/// Finds the handle of the image which loaded the running one.
+///
+/// For an application started by the boot manager, this is usually the
+/// firmware's own image. Fails with `NOT_FOUND` if the firmware did not record
+/// a parent.
+pub fn parent_image_handle() -> Result<Handle> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let image = find_image_handle()?.log();
+    let loaded_image = bt.handle_protocol::<LoadedImage>(image)?.log();
+    unsafe { (*loaded_image.get()).parent_handle() }
+        .map(|handle| handle.into())
+        .ok_or_else(|| Status::NOT_FOUND.into())
+}
+
+/// Unloads the image `handle`, releasing its resources.
+///
+/// This is meant for images which were loaded but ended up not being started,
+/// e.g. because a loader decided not to boot them, which can always be
+/// unloaded. Images which were started and are still resident, such as
+/// drivers, are only unloaded if they support it, otherwise this fails with
+/// the status of their unload function, usually `UNSUPPORTED`.
+pub fn unload_image(handle: Handle) -> Result {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    bt.unload_image(handle)
+}
+
+/// Offset of the pointer to the PE header in the DOS header
+const PE_HEADER_POINTER_OFFSET: usize = 0x3C;
+
+/// Size of the PE signature and COFF file header
+const COFF_HEADER_END: usize = 24;
+
+/// Offset of the checksum field in the optional header, for PE32 and PE32+
+const CHECKSUM_OFFSET: usize = 64;
+
+/// Checks that the checksum in the header of the PE image `data` matches its
+/// contents.
+///
+/// The checksum is recomputed the way the Windows `CheckSumMappedFile`
+/// function does it, with the checksum field itself taken as zero. Note that
+/// many linkers leave the field set to zero, in which case this returns
+/// `false`. Fails with `INVALID_PARAMETER` if the headers are malformed.
+pub fn verify_pe_checksum(data: &[u8]) -> Result<bool> {
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    if data.get(..2) != Some(b"MZ") {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let pe_header = read_u32(PE_HEADER_POINTER_OFFSET).ok_or(Status::INVALID_PARAMETER)? as usize;
+    if data.get(pe_header..pe_header + 4) != Some(b"PE\0\0") {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let checksum_offset = pe_header + COFF_HEADER_END + CHECKSUM_OFFSET;
+    let expected = read_u32(checksum_offset).ok_or(Status::INVALID_PARAMETER)?;
+
+    let mut sum = 0u32;
+    for (i, chunk) in data.chunks(2).enumerate() {
+        let offset = i * 2;
+        if (checksum_offset..checksum_offset + 4).contains(&offset) {
+            continue;
+        }
+        let word = u32::from(chunk[0]) | chunk.get(1).map_or(0, |&b| u32::from(b) << 8);
+        sum += word;
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    let actual = (sum & 0xFFFF).wrapping_add(data.len() as u32);
+
+    Ok((actual == expected).into())
+}
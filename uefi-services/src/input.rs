@@ -0,0 +1,90 @@
+//! Input device helpers.
+
+use core::time::Duration;
+
+use uefi::prelude::*;
+use uefi::proto::console::pointer::{Pointer, PointerState};
+use uefi::proto::console::text::Key;
+use uefi::{Event, Result};
+
+use crate::wait_with_timeout;
+
+/// A pointer device such as a mouse, as returned by `open_pointer`.
+pub struct PointerDevice {
+    handle: Handle,
+    pointer: &'static mut Pointer<'static>,
+}
+
+impl PointerDevice {
+    /// Handle of the pointer device.
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    /// Event signaled when the state of the device changed, to be used with
+    /// `BootServices::wait_for_event`.
+    pub fn wait_event(&self) -> Event {
+        self.pointer.wait_for_input_event()
+    }
+
+    /// Retrieves the movement and button state of the device, if it changed
+    /// since the last call.
+    pub fn state(&mut self) -> Result<Option<PointerState>> {
+        self.pointer.read_state()
+    }
+}
+
+/// Opens the first pointer device of the system, and resets it.
+///
+/// Fails with `UNSUPPORTED` if there is no pointer device.
+pub fn open_pointer() -> Result<PointerDevice> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let handle = *bt
+        .find_handles::<Pointer>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log()
+        .first()
+        .ok_or(Status::UNSUPPORTED)?;
+    let pointer = bt.handle_protocol::<Pointer>(handle)?.log();
+    let pointer = unsafe { &mut *pointer.get() };
+
+    pointer.reset(false)?.log();
+    Ok(PointerDevice { handle, pointer }.into())
+}
+
+/// Discards the keystrokes waiting in the standard input's buffer.
+///
+/// This resets the input device. Some devices fail to reset with
+/// `DEVICE_ERROR`, their pending keystrokes are then read and dropped
+/// instead.
+pub fn flush_input() -> Result {
+    let stdin = unsafe { crate::system_table().as_ref().stdin() };
+    match stdin.reset(false) {
+        Err(err) if err.status() == Status::DEVICE_ERROR => {
+            while let Ok(completion) = stdin.read_key() {
+                if completion.log().is_none() {
+                    break;
+                }
+            }
+            Status::SUCCESS.into()
+        }
+        result => result,
+    }
+}
+
+/// Waits for a key to be pressed on the standard input, for at most `timeout`.
+///
+/// Keystrokes which were pending before the call are discarded first, so that
+/// only a key pressed during the wait counts. Returns the key, or `None` if
+/// the timeout expired first. This is meant for countdowns such as "press a
+/// key to enter setup".
+pub fn wait_for_key_timeout(timeout: Duration) -> Result<Option<Key>> {
+    flush_input()?.log();
+
+    let stdin = unsafe { crate::system_table().as_ref().stdin() };
+    let key_event = stdin.wait_for_key_event();
+    if !wait_with_timeout(&key_event, timeout)?.log() {
+        return Ok(None.into());
+    }
+    stdin.read_key()
+}
@@ -10,25 +10,126 @@
 //! through the reference provided by `system_table`.
 
 #![no_std]
+#![feature(abi_efiapi)]
 #![feature(alloc_error_handler)]
 #![feature(asm)]
 #![feature(lang_items)]
-#![feature(panic_info_message)]
+#![cfg_attr(not(feature = "stable_panic"), feature(panic_info_message))]
 
+extern crate alloc;
 #[macro_use]
 extern crate log;
 // Core types.
 extern crate uefi;
 
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
 
 use cfg_if::cfg_if;
 
 use uefi::prelude::*;
 use uefi::table::boot::{EventType, Tpl};
+use uefi::table::runtime::RuntimeServices;
 use uefi::table::{Boot, SystemTable};
 use uefi::{Event, Result};
 
+mod acpi;
+mod config;
+mod console;
+mod cpu;
+mod crc;
+mod debug;
+mod device_path;
+mod driver;
+mod event;
+mod fs;
+mod graphics;
+mod handle;
+mod hash;
+mod hob;
+mod image;
+mod input;
+mod logging;
+mod memory;
+mod network;
+mod parse;
+mod pci;
+mod pxe;
+mod report;
+mod smbios;
+mod socket;
+mod storage;
+mod time;
+mod variables;
+mod watchdog;
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+pub use self::acpi::{boot_logo, BootLogoInfo};
+pub use self::config::log_config_table;
+pub use self::console::{
+    console_attribute, refresh_logger_console, set_active_console, set_console_attribute,
+    set_largest_text_mode,
+};
+pub use self::cpu::{
+    current_arch, hypervisor_vendor, is_virtualized, phys_address_bits, register_exception_handler,
+    Arch,
+};
+pub use self::crc::{crc32, verify_system_table};
+#[cfg(target_arch = "x86_64")]
+pub use self::debug::debugcon_sink;
+pub use self::debug::{hexdump, mark, set_raw_sink};
+pub use self::device_path::{device_path_to_string, device_paths_equal};
+pub use self::driver::{driver_bindings, DriverBindingInfo};
+pub use self::event::{
+    drain_events, on_protocol_installed, on_ready_to_boot, poll_event, wait_with_timeout,
+};
+pub use self::fs::{
+    file_size, find_file_anywhere, image_root_dir, list_dir, open_file, read_file_chunked,
+    FileInfoEntry,
+};
+pub use self::graphics::{
+    apply_saved_gop_mode, current_gop_mode, gop_blt_buffer, gop_fill_rect, reserve_framebuffer,
+    save_gop_mode, GopModeInfo,
+};
+pub use self::handle::handle_supports;
+pub use self::hash::sha256;
+pub use self::hob::{boot_mode, BootMode};
+pub use self::image::{parent_image_handle, unload_image, verify_pe_checksum};
+pub use self::input::{flush_input, open_pointer, wait_for_key_timeout, PointerDevice};
+pub use self::logging::{
+    clear_captured_logs, log_block, log_scope, resize_captured_logs, set_log_sink, ScopeGuard,
+};
+pub use self::memory::{
+    alloc_typed, free_by_type, largest_free_region, memory_attributes, memory_attributes_table,
+    memory_map_for_handoff, memory_pressure, poison_free_memory,
+};
+pub use self::network::{open_network, NetworkHandle};
+pub use self::parse::parse_struct;
+pub use self::pci::{pci_config_read, pci_config_write, pci_devices, PciDevice};
+pub use self::pxe::pxe_download;
+pub use self::report::{boot_report, BootReport};
+pub use self::smbios::system_uuid;
+pub use self::socket::{tcp4_connect, udp4_socket, Tcp4Socket, Udp4Socket};
+pub use self::storage::{
+    block_devices, install_ramdisk, read_blocks, read_gpt, BlockDeviceInfo, GptPartition,
+};
+#[cfg(target_arch = "x86_64")]
+pub use self::time::tsc_frequency;
+pub use self::time::{boot_services_duration_ticks, unix_time, unix_timestamp};
+pub use self::variables::{
+    boot_timeout, clear_boot_next, enumerate_variables, get_variable_typed, set_boot_next,
+    set_boot_timeout, set_variable_typed, VariableAttributesBuilder,
+};
+pub use self::watchdog::{arm_watchdog, watchdog_pause, WatchdogGuard};
+#[cfg(all(target_arch = "x86_64", feature = "cmos_rtc"))]
+pub use self::x86_64::read_cmos_time;
+#[cfg(target_arch = "x86_64")]
+pub use self::x86_64::{
+    build_identity_map, handoff, install_fault_handlers, setup_minimal_gdt, with_stack,
+    GDT_CODE_SELECTOR, GDT_DATA_SELECTOR,
+};
+
 /// Reference to the system table.
 ///
 /// This table is only fully safe to use until UEFI boot services have been exited.
@@ -36,9 +137,24 @@ use uefi::{Event, Result};
 /// UEFI's ExitBootServices entry point for more details.
 static mut SYSTEM_TABLE: Option<SystemTable<Boot>> = None;
 
+/// Pointer to the runtime services table.
+///
+/// Unlike the system table, this is kept after boot services have been exited.
+static mut RUNTIME_SERVICES: *const RuntimeServices = ptr::null();
+
+/// Handle of the running image, if it was passed to `init_with_image`
+static mut IMAGE_HANDLE: Option<Handle> = None;
+
 /// Global logger object
 static mut LOGGER: Option<uefi::logger::Logger> = None;
 
+/// Maximal number of user callbacks which can be run on exit from boot services
+const MAX_EXIT_BOOT_SERVICES_CALLBACKS: usize = 8;
+
+/// User callbacks to be run on exit from boot services, in registration order
+static mut EXIT_BOOT_SERVICES_CALLBACKS: [Option<fn()>; MAX_EXIT_BOOT_SERVICES_CALLBACKS] =
+    [None; MAX_EXIT_BOOT_SERVICES_CALLBACKS];
+
 /// Obtains a pointer to the system table.
 ///
 /// This is meant to be used by higher-level libraries,
@@ -56,6 +172,81 @@ pub fn system_table() -> NonNull<SystemTable<Boot>> {
     }
 }
 
+/// Obtains the handle of the running image.
+///
+/// `init_with_image` must have been called first by the UEFI app, otherwise
+/// this panics.
+pub fn image_handle() -> Handle {
+    unsafe { IMAGE_HANDLE.expect("The image handle is not available") }
+}
+
+/// Query the revision of the UEFI specification implemented by the firmware.
+///
+/// Returns the `(major, minor)` pair from the system table header. The minor
+/// revision encodes the two decimal digits of the version, so UEFI 2.70 is
+/// reported as `(2, 70)`.
+pub fn uefi_revision() -> (u16, u16) {
+    let rev = unsafe { system_table().as_ref() }.uefi_revision();
+    (rev.major(), rev.minor())
+}
+
+#[cfg(feature = "alloc_stats")]
+pub use uefi::alloc::AllocStats;
+
+/// Query the statistics of the global allocator.
+///
+/// Comparing the outstanding byte count before and after an operation is a
+/// simple way to check that it does not leak memory.
+#[cfg(feature = "alloc_stats")]
+pub fn alloc_stats() -> AllocStats {
+    uefi::alloc::stats()
+}
+
+/// Query the number of allocations performed by the global allocator.
+///
+/// Tests can snapshot this counter before and after running some code to
+/// assert that it does not allocate.
+#[cfg(feature = "alloc_stats")]
+pub fn allocation_count() -> u64 {
+    uefi::alloc::allocation_count()
+}
+
+/// Obtains a pointer to the runtime services table.
+///
+/// This is meant to be handed over to an operating system kernel, which can
+/// then call `SetVirtualAddressMap` and the other runtime services itself.
+///
+/// The pointer remains valid after boot services are exited, but only until
+/// the kernel remaps the runtime services memory. It is null if `init` has
+/// not been called.
+pub fn runtime_services_ptr() -> *const RuntimeServices {
+    unsafe { RUNTIME_SERVICES }
+}
+
+/// Register a function to be called on exit from UEFI boot services.
+///
+/// The callbacks are run in registration order, from the library's own exit
+/// boot services notification and before logging and memory allocation get
+/// disabled. Like any such notification, they must not call boot services
+/// which allocate memory.
+///
+/// At most 8 callbacks can be registered, after that this function fails
+/// with `OUT_OF_RESOURCES`.
+pub fn on_exit_boot_services(f: fn()) -> Result {
+    unsafe {
+        match EXIT_BOOT_SERVICES_CALLBACKS
+            .iter_mut()
+            .find(|slot| slot.is_none())
+        {
+            Some(slot) => {
+                *slot = Some(f);
+                Status::SUCCESS.into()
+            }
+            None => Status::OUT_OF_RESOURCES.into(),
+        }
+    }
+}
+
 /// Initialize the UEFI utility library.
 ///
 /// This must be called as early as possible,
@@ -69,27 +260,78 @@ pub fn init(st: &SystemTable<Boot>) -> Result {
 
         // Setup the system table singleton
         SYSTEM_TABLE = Some(st.unsafe_clone());
+        RUNTIME_SERVICES = st.runtime_services();
+        time::record_init();
 
-        // Setup logging and memory allocation
+        // Schedule these tools to be disabled on exit from UEFI boot services,
+        // before setting them up, so that they can never outlive boot services
         let boot_services = st.boot_services();
-        init_logger(st);
-        uefi::alloc::init(boot_services);
-
-        // Schedule these tools to be disabled on exit from UEFI boot services
-        boot_services
+        let registered = boot_services
             .create_event(
                 EventType::SIGNAL_EXIT_BOOT_SERVICES,
                 Tpl::NOTIFY,
                 Some(exit_boot_services),
             )
-            .map_inner(|_| ())
+            .map_inner(|_| ());
+        if registered.is_err() {
+            SYSTEM_TABLE = None;
+            return registered;
+        }
+
+        // Setup logging and memory allocation
+        #[cfg(not(feature = "no_logging"))]
+        init_logger(st);
+        uefi::alloc::init(boot_services);
+
+        // Let the panic handler wait for a known time once boot services are gone
+        #[cfg(not(feature = "no_panic_handler"))]
+        time::calibrate_busy_loop(boot_services);
+
+        // Let the logger's deferred mode write out records at low priority.
+        // Without the event, the records are only written out by flushes.
+        #[cfg(not(feature = "no_logging"))]
+        match boot_services.create_event(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(drain_logger),
+        ) {
+            Ok(drain_event) => LOGGER
+                .as_ref()
+                .unwrap()
+                .set_drain_event(boot_services, Some(drain_event.log())),
+            Err(err) => warn!(
+                "Failed to create the logger's drain event: {:?}",
+                err.status()
+            ),
+        }
+
+        #[cfg(feature = "report")]
+        report::prepare_exit_report();
+
+        registered
     }
 }
 
+/// Initialize the UEFI utility library, like `init`, and store the handle of
+/// the running image.
+///
+/// The handle is the one passed to the entry point of the app. It can then be
+/// queried with `image_handle`, and is used by the helpers which work relative
+/// to the running image, such as `image_root_dir`, instead of looking it up.
+pub fn init_with_image(image: Handle, st: &SystemTable<Boot>) -> Result {
+    unsafe {
+        IMAGE_HANDLE = Some(image);
+    }
+    init(st)
+}
+
 /// Set up logging
 ///
+/// Skipped by the `no_logging` feature, which leaves the global logger unset.
+///
 /// This is unsafe because you must arrange for the logger to be reset with
 /// disable() on exit from UEFI boot services.
+#[cfg(not(feature = "no_logging"))]
 unsafe fn init_logger(st: &SystemTable<Boot>) {
     let stdout = st.stdout();
 
@@ -99,11 +341,46 @@ unsafe fn init_logger(st: &SystemTable<Boot>) {
         LOGGER.as_ref().unwrap()
     };
 
+    // Allow rate limiting, the real time clock usually has a one second resolution
+    logger.set_clock(Some(time::logger_clock));
+
     // Set the logger.
     log::set_logger(logger).unwrap(); // Can only fail if already initialized.
 
-    // Log everything.
-    log::set_max_level(log::LevelFilter::Info);
+    // Log everything up to the level selected through cargo features.
+    cfg_if! {
+        if #[cfg(feature = "log_level_trace")] {
+            log::set_max_level(log::LevelFilter::Trace);
+        } else if #[cfg(feature = "log_level_debug")] {
+            log::set_max_level(log::LevelFilter::Debug);
+        } else if #[cfg(feature = "log_level_warn")] {
+            log::set_max_level(log::LevelFilter::Warn);
+        } else if #[cfg(feature = "log_level_error")] {
+            log::set_max_level(log::LevelFilter::Error);
+        } else {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+    }
+}
+
+/// Obtains a reference to the global logger.
+///
+/// This can be used to configure the logger, for example to enable its
+/// deferred mode. Returns `None` if `init` has not been called yet or if boot
+/// services have been exited.
+pub fn logger() -> Option<&'static uefi::logger::Logger> {
+    unsafe {
+        SYSTEM_TABLE.as_ref()?;
+        LOGGER.as_ref()
+    }
+}
+
+/// Write out the records buffered by the logger's deferred mode
+#[cfg(not(feature = "no_logging"))]
+fn drain_logger(_e: Event) {
+    if let Some(logger) = unsafe { LOGGER.as_ref() } {
+        logger.flush_deferred();
+    }
 }
 
 /// Notify the utility library that boot services are not safe to call anymore
@@ -114,59 +391,191 @@ fn exit_boot_services(_e: Event) {
     //        check that the callback does get called.
     //
     // info!("Shutting down the UEFI utility library");
+    time::record_exit_boot_services();
+    #[cfg(all(feature = "report", target_arch = "x86_64"))]
+    report::emit_exit_report();
     unsafe {
+        for callback in EXIT_BOOT_SERVICES_CALLBACKS.iter().flatten() {
+            callback();
+        }
+
         SYSTEM_TABLE = None;
-        if let Some(ref mut logger) = LOGGER {
+        if let Some(ref logger) = LOGGER {
+            // Records buffered by the deferred mode would be lost otherwise.
+            // Unlike a full flush, this does not write the NVRAM sink's
+            // variable, which is not allowed at this notification's level.
+            logger.flush_deferred();
             logger.disable();
         }
     }
     uefi::alloc::exit_boot_services();
 }
 
+/// Exit code reported to QEMU by the panic handler, 0 if none was requested
+static PANIC_CODE: AtomicU32 = AtomicU32::new(0);
+
+/// Panics, reporting `code` as the failure code when exiting QEMU.
+///
+/// The QEMU debug exit device turns the code into an exit status of
+/// `(code << 1) | 1`. Note that code 1 thus maps to the status used for
+/// success, and that code 0 is the same as a regular panic.
+pub fn panic_with_code(code: u32) -> ! {
+    PANIC_CODE.store(code, Ordering::Relaxed);
+    panic!("Failed with code {}", code);
+}
+
+/// MMIO register written by the panic handler, 0 if none was registered
+#[cfg(all(target_arch = "aarch64", feature = "panic_mmio"))]
+static PANIC_MMIO_ADDR: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// Value written to `PANIC_MMIO_ADDR` by the panic handler
+#[cfg(all(target_arch = "aarch64", feature = "panic_mmio"))]
+static PANIC_MMIO_VALUE: AtomicU32 = AtomicU32::new(0);
+
+/// Registers a 32-bit MMIO write to be performed by the panic handler.
+///
+/// On boards without a debug port, this can be used to light an LED, drive a
+/// GPIO or set a flag visible to a watchdog when the application panics. The
+/// write happens right after the panic message is logged. Passing an address
+/// of 0 disables it again.
+///
+/// # Safety
+///
+/// `addr` must be a 4-byte aligned register which is mapped by the firmware,
+/// and writing `value` to it must not compromise the rest of the system.
+#[cfg(all(target_arch = "aarch64", feature = "panic_mmio"))]
+pub unsafe fn set_panic_mmio(addr: usize, value: u32) {
+    PANIC_MMIO_VALUE.store(value, Ordering::Relaxed);
+    PANIC_MMIO_ADDR.store(addr, Ordering::Release);
+}
+
+/// Performs the MMIO write registered with `set_panic_mmio`, if any
+#[cfg(all(target_arch = "aarch64", feature = "panic_mmio"))]
+fn signal_panic_mmio() {
+    let addr = PANIC_MMIO_ADDR.load(Ordering::Acquire);
+    if addr == 0 {
+        return;
+    }
+    let value = PANIC_MMIO_VALUE.load(Ordering::Relaxed);
+    unsafe {
+        // Device memory must be accessed with a single store of the register's
+        // width, which a volatile write of an aligned u32 guarantees
+        ptr::write_volatile(addr as *mut u32, value);
+        // Make sure the write reached the device before we stall or halt
+        asm!("dsb sy", options(nostack, preserves_flags));
+    }
+}
+
+/// Exits QEMU with a failure status built from `code`, 0 being the generic
+/// failure code
+///
+/// This returns if not running in QEMU with the `qemu` feature on x86_64.
+fn exit_qemu(code: u32) {
+    // Use the f4 exit port to signal the error and exit. The port cannot exist
+    // on physical machines, so don't poke it there.
+    if cfg!(feature = "qemu") && is_virtualized() {
+        cfg_if! {
+            if #[cfg(target_arch = "x86_64")] {
+                use qemu_exit::QEMUExit;
+                let custom_exit_success = 3;
+                let qemu_exit_handle = qemu_exit::X86::new(0xF4, custom_exit_success);
+                match code {
+                    0 => qemu_exit_handle.exit_failure(),
+                    code => qemu_exit_handle.exit(code),
+                }
+            } else if #[cfg(target_arch = "aarch64")] {
+                // unimplemented!();
+                let _ = code;
+            }
+        }
+    }
+}
+
+/// Reports the failure of a `uefi_assert!` assertion
+#[cfg(feature = "qemu")]
+#[doc(hidden)]
+pub fn __assert_failed(args: core::fmt::Arguments) -> ! {
+    error!("Assertion failed: {}", args);
+    exit_qemu(0);
+    panic!("Assertion failed: {}", args);
+}
+
+/// Makes the first record logged at the `Error` level fail the run, or stop
+/// doing so.
+///
+/// Once enabled, QEMU is exited with a failure status right after an error is
+/// written, which catches errors that tests would otherwise log and carry on
+/// from. The status is the one of `panic_with_code` if the error comes from
+/// it. This does nothing outside of QEMU, or if `init` has not been called.
+#[cfg(feature = "qemu")]
+pub fn set_fail_on_error(fail: bool) {
+    if let Some(logger) = logger() {
+        logger.set_error_hook(if fail { Some(fail_on_error) } else { None });
+    }
+}
+
+/// Exits QEMU once an error has been logged, see `set_fail_on_error`
+#[cfg(feature = "qemu")]
+fn fail_on_error() {
+    exit_qemu(PANIC_CODE.load(Ordering::Relaxed));
+}
+
+/// Asserts that a condition holds in an integration test.
+///
+/// On failure, the message is logged and QEMU is exited right away with a
+/// failure status, skipping the delay of the panic handler. Outside of QEMU,
+/// this panics like `assert!`.
+#[cfg(feature = "qemu")]
+#[macro_export]
+macro_rules! uefi_assert {
+    ($cond:expr $(,)?) => {
+        $crate::uefi_assert!($cond, "{}", core::stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::__assert_failed(core::format_args!($($arg)+));
+        }
+    };
+}
+
 #[lang = "eh_personality"]
 fn eh_personality() {}
 
 #[cfg(not(feature = "no_panic_handler"))]
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-    if let Some(location) = info.location() {
-        error!(
-            "Panic in {} at ({}, {}):",
-            location.file(),
-            location.line(),
-            location.column()
-        );
-        if let Some(message) = info.message() {
-            error!("{}", message);
+    cfg_if! {
+        if #[cfg(feature = "stable_panic")] {
+            // The message cannot be extracted on its own, print the whole info
+            error!("{}", info);
+        } else {
+            if let Some(location) = info.location() {
+                error!(
+                    "Panic in {} at ({}, {}):",
+                    location.file(),
+                    location.line(),
+                    location.column()
+                );
+                if let Some(message) = info.message() {
+                    error!("{}", message);
+                }
+            }
         }
     }
 
+    // Signal the panic to the board, for headless systems
+    #[cfg(all(target_arch = "aarch64", feature = "panic_mmio"))]
+    signal_panic_mmio();
+
     // Give the user some time to read the message
     if let Some(st) = unsafe { SYSTEM_TABLE.as_ref() } {
         st.boot_services().stall(10_000_000);
     } else {
-        let mut dummy = 0u64;
-        // FIXME: May need different counter values in debug & release builds
-        for i in 0..300_000_000 {
-            unsafe {
-                core::ptr::write_volatile(&mut dummy, i);
-            }
-        }
+        time::busy_wait(core::time::Duration::from_secs(10));
     }
 
-    // If running in QEMU, use the f4 exit port to signal the error and exit
-    if cfg!(feature = "qemu") {
-        cfg_if! {
-            if #[cfg(target_arch = "x86_64")] {
-                use qemu_exit::QEMUExit;
-                let custom_exit_success = 3;
-                let qemu_exit_handle = qemu_exit::X86::new(0xF4, custom_exit_success);
-                qemu_exit_handle.exit_failure();
-            } else if #[cfg(target_arch = "aarch64")] {
-                // unimplemented!();
-            }
-        }
-    }
+    // If running in QEMU, exit it with an error code
+    exit_qemu(PANIC_CODE.load(Ordering::Relaxed));
 
     // If the system table is available, use UEFI's standard shutdown mechanism
     if let Some(st) = unsafe { SYSTEM_TABLE.as_ref() } {
@@ -204,6 +613,26 @@ fn panic_handler(info: &core::panic::PanicInfo) -> ! {
 #[cfg(not(feature = "no_alloc_handler"))]
 #[alloc_error_handler]
 fn out_of_memory(layout: ::core::alloc::Layout) -> ! {
+    use core::sync::atomic::AtomicBool;
+
+    // Logging does not allocate, but only report once should that change
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+    if !REPORTED.swap(true, Ordering::Relaxed) {
+        error!(
+            "Failed to allocate {} bytes with an alignment of {}",
+            layout.size(),
+            layout.align()
+        );
+        #[cfg(feature = "alloc_stats")]
+        {
+            let stats = alloc_stats();
+            error!(
+                "{} bytes were outstanding, with a peak of {} bytes",
+                stats.outstanding, stats.peak
+            );
+        }
+    }
+
     panic!(
         "Ran out of free memory while trying to allocate {:#?}",
         layout
@@ -0,0 +1,137 @@
+//! Logging helpers.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{fmt, ptr};
+
+use log::Level;
+use uefi::prelude::*;
+use uefi::Result;
+
+/// Discards the log text captured so far.
+///
+/// The only captured log is the one kept by the logger's NVRAM sink, see
+/// `Logger::set_nvram_sink`. Its variable is emptied the next time it is
+/// written, so a test can clear the log, run, and only find the text of that
+/// run in it. This does nothing if the logger is not available.
+pub fn clear_captured_logs() {
+    if let Some(logger) = crate::logger() {
+        logger.clear_nvram_log();
+    }
+}
+
+/// Storage of the captured log allocated by `resize_captured_logs`, if any
+static mut CAPTURE_STORAGE: *const u8 = ptr::null();
+
+/// Changes the amount of text kept by the captured log to `new_size` bytes.
+///
+/// The captured log is the one of the logger's NVRAM sink, which holds 16 KiB
+/// by default. The most recent lines which fit are preserved. Its new storage
+/// is allocated from the heap, this thus fails with `UNSUPPORTED` once boot
+/// services have been exited, and with `INVALID_PARAMETER` if `new_size` is
+/// zero. It fails with `NOT_READY` if called while the variable is being
+/// written, e.g. from a notification function. Keep in mind that firmware
+/// limits the size of variables, which may prevent large logs from being
+/// written.
+pub fn resize_captured_logs(new_size: usize) -> Result {
+    let logger = crate::logger().ok_or(Status::UNSUPPORTED)?;
+    if new_size == 0 {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let storage = Box::leak(vec![0u8; new_size].into_boxed_slice());
+    let storage_ptr = storage.as_ptr();
+    match logger.set_nvram_log_storage(storage) {
+        Ok(previous) => unsafe {
+            // Only free storage which was allocated here
+            if let Some(previous) = previous.filter(|p| p.as_ptr() == CAPTURE_STORAGE) {
+                drop(Box::from_raw(previous));
+            }
+            CAPTURE_STORAGE = storage_ptr;
+            Ok(().into())
+        },
+        Err(storage) => {
+            drop(unsafe { Box::from_raw(storage) });
+            Err(Status::NOT_READY.into())
+        }
+    }
+}
+
+/// Sends log records to `sink`, in addition to the standard output.
+///
+/// This lets logs reach custom output devices, such as a UART or a
+/// framebuffer console driven by the application. Records are written to the
+/// sink as they are logged, and it is no longer written to once boot services
+/// have been exited. Does nothing if `init` has not been called.
+pub fn set_log_sink(sink: &'static mut dyn fmt::Write) {
+    if let Some(logger) = crate::logger() {
+        logger.set_sink(Some(sink));
+    }
+}
+
+/// Lines of text displayed one after the other
+struct Lines<'a>(&'a [&'a str]);
+
+impl fmt::Display for Lines<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, line) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Logs several lines of text as a single record.
+///
+/// The logger writes each record in one go, so other records cannot end up
+/// in the middle of the block, and every line gets the level prefix. Nothing
+/// is logged if `level` is filtered out.
+pub fn log_block(level: Level, lines: &[&str]) {
+    log!(level, "{}", Lines(lines));
+}
+
+/// Number of scopes opened with `log_scope` and not closed yet
+static SCOPE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Deepest nesting level which is reflected in the indentation of records
+const MAX_INDENTED_DEPTH: usize = 16;
+
+/// Number of spaces of indentation per nesting level
+const INDENT_PER_DEPTH: usize = 2;
+
+/// Indent the following records according to the nesting `depth`
+fn set_depth(depth: usize) {
+    SCOPE_DEPTH.store(depth, Ordering::Relaxed);
+    if let Some(logger) = crate::logger() {
+        logger.set_indent(depth.min(MAX_INDENTED_DEPTH) * INDENT_PER_DEPTH);
+    }
+}
+
+/// Guard closing a scope opened with `log_scope` when dropped.
+#[must_use = "the scope is closed as soon as the guard is dropped"]
+pub struct ScopeGuard<'a> {
+    label: &'a str,
+}
+
+impl Drop for ScopeGuard<'_> {
+    fn drop(&mut self) {
+        set_depth(SCOPE_DEPTH.load(Ordering::Relaxed).saturating_sub(1));
+        info!("leaving {}", self.label);
+    }
+}
+
+/// Opens a nested scope in the log, until the returned guard is dropped.
+///
+/// An "entering" line is logged, and the records which follow are indented
+/// by two more spaces, up to a maximum of 16 nesting levels. Dropping the
+/// guard undoes the indentation and logs a "leaving" line, so nested boot
+/// phases show up as a tree.
+pub fn log_scope(label: &str) -> ScopeGuard {
+    info!("entering {}", label);
+    set_depth(SCOPE_DEPTH.load(Ordering::Relaxed) + 1);
+    ScopeGuard { label }
+}
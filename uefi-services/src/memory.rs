@@ -0,0 +1,220 @@
+//! Memory map helpers.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{mem, ptr, slice};
+
+use uefi::prelude::*;
+use uefi::table::boot::{AllocateType, MemoryDescriptor, MemoryType, Tpl};
+use uefi::table::cfg::MEMORY_ATTRIBUTES_TABLE_GUID;
+use uefi::Result;
+
+/// Size of the pages described by the memory map
+pub(crate) const PAGE_SIZE: u64 = 4096;
+
+/// Retrieves a copy of the current memory map
+pub(crate) fn memory_map() -> Result<Vec<MemoryDescriptor>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    // Allocating the buffer may split descriptors, so leave some headroom
+    let desc_size = mem::size_of::<MemoryDescriptor>();
+    let len = bt.memory_map_size() / desc_size + 8;
+    let mut storage = vec![MemoryDescriptor::default(); len];
+    let buffer =
+        unsafe { slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, len * desc_size) };
+
+    bt.memory_map(buffer)
+        .map_inner(|(_key, descriptors)| descriptors.copied().collect())
+}
+
+/// Sums the page counts of the memory map by memory type.
+///
+/// Free memory is accounted as `CONVENTIONAL`, while memory which only becomes
+/// free after exiting boot services appears as the boot services and loader
+/// types. Comparing the totals of the types shows how memory is used, e.g.
+/// which unexpected reservations eat into it. Note that the memory map is
+/// fetched into an allocated buffer, which slightly changes it.
+pub fn free_by_type() -> Result<BTreeMap<MemoryType, u64>> {
+    let mut pages = BTreeMap::new();
+    for desc in memory_map()?.log() {
+        *pages.entry(desc.ty).or_insert(0) += desc.page_count;
+    }
+    Ok(pages.into())
+}
+
+/// Computes the fraction of general purpose memory which is still free.
+///
+/// General purpose memory is the memory which is free once boot services are
+/// exited: the conventional memory and the memory allocated by loaders and
+/// boot services. The result ranges from 0.0, when all of it is allocated, to
+/// 1.0. The memory map fetch allocates a little memory, so the value is an
+/// approximation, but it is good enough to decide whether to skip optional
+/// work such as caching. Fails with `NOT_FOUND` if the memory map has no
+/// general purpose memory.
+pub fn memory_pressure() -> Result<f32> {
+    let pages = free_by_type()?.log();
+    let count = |ty| pages.get(&ty).copied().unwrap_or(0);
+
+    let free = count(MemoryType::CONVENTIONAL);
+    let total = free
+        + count(MemoryType::LOADER_CODE)
+        + count(MemoryType::LOADER_DATA)
+        + count(MemoryType::BOOT_SERVICES_CODE)
+        + count(MemoryType::BOOT_SERVICES_DATA);
+    if total == 0 {
+        return Err(Status::NOT_FOUND.into());
+    }
+    Ok((free as f32 / total as f32).into())
+}
+
+/// Queries the attributes of the memory containing the physical address `addr`.
+///
+/// This returns the raw `MemoryAttribute` bits of the memory map descriptor
+/// which contains the address, telling for example which caching modes the
+/// memory supports. Fails with `NOT_FOUND` if no descriptor contains it.
+pub fn memory_attributes(addr: u64) -> Result<u64> {
+    memory_map()?
+        .log()
+        .iter()
+        .find(|desc| {
+            let size = desc.page_count.saturating_mul(PAGE_SIZE);
+            addr >= desc.phys_start && addr - desc.phys_start < size
+        })
+        .map(|desc| desc.att.bits().into())
+        .ok_or_else(|| Status::NOT_FOUND.into())
+}
+
+/// Finds the largest region of free memory.
+///
+/// This returns the physical address and page count of the largest
+/// conventional memory descriptor of the memory map, which is the biggest
+/// block that `allocate_pages` can hand out. Fails with `NOT_FOUND` if there
+/// is no free memory at all.
+pub fn largest_free_region() -> Result<(u64, usize)> {
+    memory_map()?
+        .log()
+        .iter()
+        .filter(|desc| desc.ty == MemoryType::CONVENTIONAL)
+        .max_by_key(|desc| desc.page_count)
+        .map(|desc| (desc.phys_start, desc.page_count as usize).into())
+        .ok_or_else(|| Status::NOT_FOUND.into())
+}
+
+/// Retrieves the memory map in the format expected by operating system kernels.
+///
+/// This returns the raw memory map, along with the size and version of its
+/// descriptors exactly as the firmware reports them. Kernels must step
+/// through the map using this descriptor size, which may differ from
+/// `size_of::<MemoryDescriptor>()`. The buffer is 8-byte aligned.
+pub fn memory_map_for_handoff() -> Result<(Vec<u8>, usize, u32)> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let mut size = bt.memory_map_size();
+    loop {
+        // Allocating the buffer may split descriptors, so leave some headroom
+        size += 8 * mem::size_of::<MemoryDescriptor>();
+        let mut buffer = vec![0; size];
+        match bt.raw_memory_map(&mut buffer) {
+            Ok(completion) => {
+                let (status, (_key, layout)) = completion.split();
+                buffer.truncate(layout.map_size);
+                return status.into_with_val(|| (buffer, layout.desc_size, layout.desc_version));
+            }
+            Err(err) if err.status() == Status::BUFFER_TOO_SMALL => size = err.data().map_size,
+            Err(err) => return Err(err.status().into()),
+        }
+    }
+}
+
+/// Allocates pages of memory of the given type.
+///
+/// `size` is rounded up to whole pages. Returns the physical address and
+/// page count of the allocation, which stays allocated for as long as boot
+/// services run and appears with this type in the final memory map. Fails
+/// with `INVALID_PARAMETER` if `size` is zero or if `AllocatePages` does not
+/// allow the memory type, as is the case of conventional and persistent
+/// memory.
+pub fn alloc_typed(size: usize, ty: MemoryType) -> Result<(u64, usize)> {
+    let allowed = match ty {
+        MemoryType::CONVENTIONAL | MemoryType::PERSISTENT_MEMORY => false,
+        // Values between the standard types and the OEM range are invalid
+        MemoryType(value) => !(15..0x7000_0000).contains(&value),
+    };
+    if size == 0 || !allowed {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let page_count = (size + PAGE_SIZE as usize - 1) / PAGE_SIZE as usize;
+    bt.allocate_pages(AllocateType::AnyPages, ty, page_count)
+        .map_inner(|address| (address, page_count))
+}
+
+/// Reads the descriptors of the memory attributes table.
+///
+/// The table describes the runtime services memory regions with the
+/// permissions their contents need, e.g. `RUNTIME_CODE` regions split in
+/// read-only code and non-executable data, so that an OS can map them without
+/// having writable and executable pages. The permissions are the
+/// `MemoryAttribute::READ_ONLY` and `MemoryAttribute::EXECUTE_PROTECT` bits.
+///
+/// Fails with `NOT_FOUND` if the firmware does not provide the table.
+pub fn memory_attributes_table() -> Result<Vec<MemoryDescriptor>> {
+    let st = unsafe { crate::system_table().as_ref() };
+    let table = st
+        .config_table()
+        .iter()
+        .find(|entry| entry.guid == MEMORY_ATTRIBUTES_TABLE_GUID)
+        .ok_or(Status::NOT_FOUND)?
+        .address as *const u32;
+
+    // The header holds the version, entry count, descriptor size and flags
+    let (entries, desc_size) = unsafe { (*table.add(1) as usize, *table.add(2) as usize) };
+    if desc_size < mem::size_of::<MemoryDescriptor>() {
+        return Err(Status::UNSUPPORTED.into());
+    }
+
+    // Descriptors may be larger than ours, so step by the reported size
+    let first = unsafe { (table as *const u8).add(16) };
+    let descriptors: Vec<MemoryDescriptor> = (0..entries)
+        .map(|i| unsafe {
+            ptr::read_unaligned(first.add(i * desc_size) as *const MemoryDescriptor)
+        })
+        .collect();
+    Ok(descriptors.into())
+}
+
+/// Fills all free memory with `pattern`, as a debugging aid.
+///
+/// Code reading memory it never initialized, e.g. a kernel trusting the
+/// contents of pages it was handed, then sees an obvious pattern instead of
+/// plausible garbage. Only conventional memory is filled, which never holds
+/// the application's image, stack or heap, as those are allocated. The first
+/// page is skipped, since writing to the null pointer is undefined behaviour.
+///
+/// The memory map is fetched and memory filled at the `NOTIFY` task priority
+/// level, the highest one at which the map can be fetched, so that no
+/// notification function can allocate memory in the meantime. It must thus
+/// be called below that level, and can take a while on machines with a lot
+/// of memory. Fails with `UNSUPPORTED` once boot services have been exited,
+/// as the memory map is no longer available.
+pub fn poison_free_memory(pattern: u8) -> Result {
+    let st = unsafe { crate::SYSTEM_TABLE.as_ref() }.ok_or(Status::UNSUPPORTED)?;
+    let bt = st.boot_services();
+
+    let _tpl = unsafe { bt.raise_tpl(Tpl::NOTIFY) };
+    let map = memory_map()?.log();
+    for desc in map
+        .iter()
+        .filter(|desc| desc.ty == MemoryType::CONVENTIONAL)
+    {
+        let start = desc.phys_start.max(PAGE_SIZE);
+        let end = desc.phys_start + desc.page_count * PAGE_SIZE;
+        if start < end {
+            // Boot services identity-map all memory
+            unsafe { ptr::write_bytes(start as *mut u8, pattern, (end - start) as usize) };
+        }
+    }
+    Ok(().into())
+}
@@ -0,0 +1,135 @@
+//! Network access.
+
+use uefi::prelude::*;
+use uefi::proto::network::snp::{NetworkState, ReceiveFlags, SimpleNetwork};
+use uefi::{Event, Result};
+
+/// Number of polls of the driver while waiting for a frame to be sent
+const TRANSMIT_POLLS: usize = 1000;
+
+/// Delay between two polls of the driver, in microseconds
+const TRANSMIT_POLL_US: usize = 100;
+
+/// A network interface, ready to send and receive frames.
+///
+/// This is obtained with `open_network`.
+pub struct NetworkHandle {
+    handle: Handle,
+    snp: &'static SimpleNetwork,
+}
+
+impl NetworkHandle {
+    /// Handle of the network interface.
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    /// Hardware address of the interface.
+    pub fn mac_address(&self) -> &[u8] {
+        let mode = self.snp.mode();
+        &mode.current_address().0[..mode.hw_address_size() as usize]
+    }
+
+    /// Size of the media header at the start of the frames, in bytes.
+    pub fn media_header_size(&self) -> usize {
+        self.snp.mode().media_header_size() as usize
+    }
+
+    /// Maximal size of the data of a frame, excluding the media header.
+    pub fn max_packet_size(&self) -> usize {
+        self.snp.mode().max_packet_size() as usize
+    }
+
+    /// Sends a complete frame, including its media header.
+    ///
+    /// The frame is copied, and this waits for the driver to be done with the
+    /// copy. It fails with `TIMEOUT` if the driver is still using it after
+    /// about 100 ms, or with the error of the driver if polling it fails. In
+    /// both cases the interface is shut down, so that the driver lets go of
+    /// the copy, and initialized again. If even that fails, the copy is
+    /// leaked. Buffers of other transmissions recycled meanwhile are dropped.
+    pub fn transmit(&self, frame: &[u8]) -> Result {
+        let bt = unsafe { crate::system_table().as_ref().boot_services() };
+        let copy = frame.to_vec();
+        unsafe { self.snp.transmit(&copy) }?.log();
+
+        let mut outcome = Err(Status::TIMEOUT.into());
+        for _ in 0..TRANSMIT_POLLS {
+            match self.snp.get_recycled_transmit_buffer() {
+                Ok(recycled) if recycled.log() == Some(copy.as_ptr() as *mut u8) => {
+                    return Ok(().into());
+                }
+                Ok(_) => bt.stall(TRANSMIT_POLL_US),
+                Err(err) => {
+                    outcome = Err(err);
+                    break;
+                }
+            }
+        }
+
+        // The driver may still read the copy, make it let go of it
+        if self.snp.shutdown().is_ok() {
+            // The original error matters more than that of the restart
+            let _ = self
+                .snp
+                .initialize(0, 0)
+                .and_then(|_| set_receive_filters(self.snp));
+        } else {
+            core::mem::forget(copy);
+        }
+        outcome
+    }
+
+    /// Receives a frame, including its media header, and returns its size.
+    ///
+    /// Fails with `NOT_READY` if no frame was received, and with
+    /// `BUFFER_TOO_SMALL` and the size of the frame if it does not fit in
+    /// `buffer`.
+    pub fn receive(&self, buffer: &mut [u8]) -> Result<usize, Option<usize>> {
+        self.snp.receive(buffer)
+    }
+
+    /// Event signaled when a frame has been received, to be used with
+    /// `BootServices::wait_for_event`.
+    pub fn wait_for_packet(&self) -> Event {
+        self.snp.wait_for_packet()
+    }
+}
+
+/// Opens the first network interface of the system.
+///
+/// The interface is started and initialized if needed, and set up to receive
+/// the frames sent to its own address and the broadcast ones. Fails with
+/// `UNSUPPORTED` if there is no network interface.
+pub fn open_network() -> Result<NetworkHandle> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let handle = *bt
+        .find_handles::<SimpleNetwork>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log()
+        .first()
+        .ok_or(Status::UNSUPPORTED)?;
+    let snp = bt.handle_protocol::<SimpleNetwork>(handle)?.log();
+    let snp = unsafe { &*snp.get() };
+
+    // Bring the interface to the initialized state, the firmware's network
+    // stack may have done part or all of it already.
+    if snp.mode().state() == NetworkState::STOPPED {
+        snp.start()?.log();
+    }
+    if snp.mode().state() == NetworkState::STARTED {
+        snp.initialize(0, 0)?.log();
+    }
+
+    set_receive_filters(snp)?.log();
+
+    Ok(NetworkHandle { handle, snp }.into())
+}
+
+/// Sets the interface up to receive the frames sent to its own address and
+/// the broadcast ones
+fn set_receive_filters(snp: &SimpleNetwork) -> Result {
+    let wanted =
+        (ReceiveFlags::UNICAST | ReceiveFlags::BROADCAST) & snp.mode().receive_filter_mask();
+    snp.receive_filters(wanted, ReceiveFlags::empty())
+}
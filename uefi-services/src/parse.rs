@@ -0,0 +1,28 @@
+//! Parsing of raw buffers returned by the firmware.
+
+use core::mem;
+
+use uefi::prelude::*;
+use uefi::Result;
+
+/// Reinterprets the start of `buf` as a `T`.
+///
+/// This checks that `buf` is large enough and suitably aligned to hold a `T`,
+/// failing with `BUFFER_TOO_SMALL` and `INVALID_PARAMETER` respectively
+/// otherwise. Bytes past the end of the `T` are ignored, e.g. the payload
+/// following a header.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid `T`, which is the case of structures made
+/// of integers and arrays of integers, but not of those holding references,
+/// `bool`s or Rust enums.
+pub unsafe fn parse_struct<T: Copy>(buf: &[u8]) -> Result<&T> {
+    if buf.len() < mem::size_of::<T>() {
+        return Err(Status::BUFFER_TOO_SMALL.into());
+    }
+    if buf.as_ptr().align_offset(mem::align_of::<T>()) != 0 {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    Ok((&*(buf.as_ptr() as *const T)).into())
+}
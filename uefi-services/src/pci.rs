@@ -0,0 +1,139 @@
+//! PCI configuration space access.
+
+use alloc::vec::Vec;
+
+use uefi::prelude::*;
+use uefi::proto::pci::root_bridge::{PciAddress, PciRootBridgeIo};
+use uefi::Result;
+
+/// Description of a PCI function, as returned by `pci_devices`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PciDevice {
+    /// Segment of the function.
+    pub segment: u16,
+    /// Bus of the function.
+    pub bus: u8,
+    /// Device number of the function on its bus.
+    pub device: u8,
+    /// Function number within the device.
+    pub function: u8,
+    /// Vendor ID.
+    pub vendor_id: u16,
+    /// Device ID, assigned by the vendor.
+    pub device_id: u16,
+    /// Base class code, such as 0x01 for mass storage controllers.
+    pub class: u8,
+    /// Sub-class code, specific to the base class.
+    pub subclass: u8,
+    /// Programming interface, specific to the sub-class.
+    pub prog_if: u8,
+    /// Revision ID, assigned by the vendor.
+    pub revision: u8,
+}
+
+/// Vendor ID read from functions which are not present
+const NO_VENDOR: u16 = 0xFFFF;
+
+/// Header type bit telling that a device implements several functions
+const MULTI_FUNCTION: u32 = 0x80 << 16;
+
+/// Runs `f` on the root bridge which produces `bus` in segment `seg`.
+fn with_root_bridge<T>(
+    seg: u16,
+    bus: u8,
+    f: impl FnOnce(&mut PciRootBridgeIo) -> Result<T>,
+) -> Result<T> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    // If there is no root bridge at all, this fails with `NOT_FOUND` as well
+    let handles = bt.find_handles::<PciRootBridgeIo>()?.log();
+
+    for handle in handles {
+        let bridge = bt.handle_protocol::<PciRootBridgeIo>(handle)?.log();
+        let bridge = unsafe { &mut *bridge.get() };
+
+        if bridge.segment_number() != u32::from(seg) {
+            continue;
+        }
+        if bridge.bus_range()?.log().contains(&bus) {
+            return f(bridge);
+        }
+    }
+
+    Err(Status::NOT_FOUND.into())
+}
+
+/// Reads a dword from the configuration space of a PCI function.
+///
+/// The offset must be dword-aligned and below 4096. Returns `NOT_FOUND` if no
+/// root bridge produces the requested bus in segment `seg`.
+pub fn pci_config_read(seg: u16, bus: u8, dev: u8, func: u8, offset: u16) -> Result<u32> {
+    with_root_bridge(seg, bus, |bridge| {
+        bridge.pci_read(PciAddress::new(bus, dev, func, offset))
+    })
+}
+
+/// Writes a dword to the configuration space of a PCI function.
+///
+/// The same restrictions as for `pci_config_read` apply.
+pub fn pci_config_write(seg: u16, bus: u8, dev: u8, func: u8, offset: u16, value: u32) -> Result {
+    with_root_bridge(seg, bus, |bridge| {
+        bridge.pci_write(PciAddress::new(bus, dev, func, offset), value)
+    })
+}
+
+/// Enumerates the PCI functions of all segments.
+///
+/// Every bus produced by a root bridge is scanned, which finds the functions
+/// behind PCI bridges as well once the firmware has assigned bus numbers to
+/// them. Functions whose vendor ID reads as 0xFFFF are not present and are
+/// skipped. Fails with `NOT_FOUND` if there is no root bridge.
+pub fn pci_devices() -> Result<Vec<PciDevice>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let mut devices = Vec::new();
+    for handle in bt.find_handles::<PciRootBridgeIo>()?.log() {
+        let bridge = bt.handle_protocol::<PciRootBridgeIo>(handle)?.log();
+        let bridge = unsafe { &*bridge.get() };
+        let segment = bridge.segment_number() as u16;
+
+        for bus in bridge.bus_range()?.log() {
+            for device in 0..32 {
+                for function in 0..8 {
+                    let read = |offset| {
+                        bridge.pci_read::<u32>(PciAddress::new(bus, device, function, offset))
+                    };
+
+                    let id = read(0x00)?.log();
+                    if id as u16 == NO_VENDOR {
+                        // Without function 0, the device does not exist
+                        if function == 0 {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    let class = read(0x08)?.log();
+                    devices.push(PciDevice {
+                        segment,
+                        bus,
+                        device,
+                        function,
+                        vendor_id: id as u16,
+                        device_id: (id >> 16) as u16,
+                        class: (class >> 24) as u8,
+                        subclass: (class >> 16) as u8,
+                        prog_if: (class >> 8) as u8,
+                        revision: class as u8,
+                    });
+
+                    if function == 0 && read(0x0C)?.log() & MULTI_FUNCTION == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(devices.into())
+}
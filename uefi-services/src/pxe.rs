@@ -0,0 +1,60 @@
+//! Network boot downloads.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use uefi::prelude::*;
+use uefi::proto::network::pxe::BaseCode;
+use uefi::{CStr8, Result};
+
+/// Downloads a file from the network boot server into memory.
+///
+/// This goes through the PXE base code protocol of the first network
+/// interface, configuring it through DHCP if that was not done yet, and
+/// fetches `filename` over TFTP from the server named by the proxy DHCP offer
+/// or by the DHCP acknowledgement.
+///
+/// Fails with `UNSUPPORTED` if there is no PXE base code protocol, with
+/// `TIMEOUT` if the DHCP or TFTP server does not answer, with `NOT_FOUND` if
+/// the DHCP server does not name a boot server, and with `TFTP_ERROR` if the
+/// server reports an error such as a missing file.
+pub fn pxe_download(filename: &str) -> Result<Vec<u8>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let handle = *bt
+        .find_handles::<BaseCode>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log()
+        .first()
+        .ok_or(Status::UNSUPPORTED)?;
+    let pxe = bt.handle_protocol::<BaseCode>(handle)?.log();
+    let pxe = unsafe { &mut *pxe.get() };
+
+    if !pxe.mode().started() {
+        pxe.start(false)?.log();
+    }
+    if pxe.mode().dhcp_ack().is_none() {
+        pxe.dhcp(true)?.log();
+    }
+
+    let server_ip = pxe
+        .mode()
+        .proxy_offer()
+        .or_else(|| pxe.mode().dhcp_ack())
+        .map(|packet| packet.dhcpv4_server_ip())
+        .filter(|ip| ip.as_v4() != [0; 4])
+        .ok_or(Status::NOT_FOUND)?;
+
+    let mut name = Vec::with_capacity(filename.len() + 1);
+    name.extend_from_slice(filename.as_bytes());
+    name.push(0);
+    let name = CStr8::from_bytes_with_nul(&name).map_err(|_| Status::INVALID_PARAMETER)?;
+
+    let size = pxe.tftp_get_file_size(&server_ip, name)?.log();
+    let mut data = vec![0; size as usize];
+    let len = pxe
+        .tftp_read_file(&server_ip, name, &mut data)
+        .map_err(|err| err.status())?
+        .log();
+    data.truncate(len as usize);
+    Ok(data.into())
+}
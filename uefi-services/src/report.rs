@@ -0,0 +1,142 @@
+//! Summary of the boot environment.
+
+use alloc::string::String;
+use core::fmt;
+
+use uefi::proto::pi::mp::MpServices;
+use uefi::table::boot::MemoryType;
+
+use crate::fs::cstr16_to_string;
+use crate::memory::{memory_map, PAGE_SIZE};
+use crate::variables::get_global_variable;
+
+/// Facts about the boot environment, as gathered by `boot_report`.
+#[derive(Debug, Clone)]
+pub struct BootReport {
+    /// Total size of the RAM described by the memory map, in bytes, 0 if the
+    /// memory map could not be read.
+    pub total_memory: u64,
+    /// Number of enabled logical processors, 1 if the firmware cannot tell.
+    pub cpu_count: usize,
+    /// Whether secure boot is enforced, `None` if the firmware does not
+    /// support it.
+    pub secure_boot: Option<bool>,
+    /// Vendor of the firmware.
+    pub firmware_vendor: String,
+    /// Firmware-specific revision number.
+    pub firmware_revision: u32,
+}
+
+impl BootReport {
+    /// Writes the report as a single line JSON object.
+    pub fn write_json(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(
+            f,
+            "{{\"total_memory\":{},\"cpu_count\":{},\"secure_boot\":",
+            self.total_memory, self.cpu_count
+        )?;
+        match self.secure_boot {
+            Some(enabled) => write!(f, "{}", enabled)?,
+            None => f.write_str("null")?,
+        }
+        f.write_str(",\"firmware_vendor\":\"")?;
+        for c in self.firmware_vendor.chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\{}", c)?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        writeln!(f, "\",\"firmware_revision\":{}}}", self.firmware_revision)
+    }
+}
+
+/// Query whether secure boot is enforced, through the `SecureBoot` variable
+fn secure_boot_enabled() -> Option<bool> {
+    let mut value = [0u8; 1];
+    match get_global_variable("SecureBoot", &mut value) {
+        Ok(completion) if completion.log() == 1 => Some(value[0] == 1),
+        _ => None,
+    }
+}
+
+/// Gathers facts about the boot environment.
+///
+/// This must be called before boot services are exited. Facts which cannot
+/// be queried are left at the defaults described in `BootReport`.
+pub fn boot_report() -> BootReport {
+    let st = unsafe { crate::system_table().as_ref() };
+    let bt = st.boot_services();
+
+    let total_memory = memory_map()
+        .map(|descriptors| {
+            descriptors
+                .log()
+                .iter()
+                .filter(|desc| {
+                    !matches!(
+                        desc.ty,
+                        MemoryType::RESERVED
+                            | MemoryType::UNUSABLE
+                            | MemoryType::MMIO
+                            | MemoryType::MMIO_PORT_SPACE
+                    )
+                })
+                .map(|desc| desc.page_count * PAGE_SIZE)
+                .sum()
+        })
+        .unwrap_or(0);
+
+    let cpu_count = bt
+        .locate_protocol::<MpServices>()
+        .ok()
+        .and_then(|mp| {
+            let mp = unsafe { &*mp.log().get() };
+            mp.get_number_of_processors().ok()
+        })
+        .map_or(1, |count| count.log().enabled);
+
+    BootReport {
+        total_memory,
+        cpu_count,
+        secure_boot: secure_boot_enabled(),
+        firmware_vendor: cstr16_to_string(st.firmware_vendor()),
+        firmware_revision: {
+            let revision = st.firmware_revision();
+            (u32::from(revision.major()) << 16) | u32::from(revision.minor())
+        },
+    }
+}
+
+/// Report gathered at initialization, written out on exit from boot services
+#[cfg(feature = "report")]
+static mut EXIT_REPORT: Option<BootReport> = None;
+
+/// Gather the report emitted on exit from boot services
+///
+/// Memory must not be allocated from the exit boot services notification, so
+/// the facts are gathered as soon as the library is initialized.
+#[cfg(feature = "report")]
+pub(crate) fn prepare_exit_report() {
+    unsafe {
+        EXIT_REPORT = Some(boot_report());
+    }
+}
+
+/// Write the report gathered at initialization to the debug console
+#[cfg(all(feature = "report", target_arch = "x86_64"))]
+pub(crate) fn emit_exit_report() {
+    /// Adapter writing text to the debug console
+    struct Debugcon;
+
+    impl fmt::Write for Debugcon {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            crate::debugcon_sink(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    if let Some(report) = unsafe { EXIT_REPORT.as_ref() } {
+        let _ = report.write_json(&mut Debugcon);
+    }
+}
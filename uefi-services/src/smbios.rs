@@ -0,0 +1,110 @@
+//! SMBIOS helpers.
+
+use core::slice;
+
+use uefi::prelude::*;
+use uefi::table::cfg::{SMBIOS3_GUID, SMBIOS_GUID};
+use uefi::Result;
+
+/// Type of the system information structure
+const SYSTEM_INFORMATION: u8 = 1;
+
+/// Type of the structure ending the structure table
+const END_OF_TABLE: u8 = 127;
+
+/// Structure table of the SMBIOS, and the version of the specification it
+/// follows
+struct StructureTable {
+    data: &'static [u8],
+    version: (u8, u8),
+}
+
+/// Locates the SMBIOS structure table, through the 64-bit entry point if
+/// there is one and the 32-bit one otherwise
+fn structure_table() -> Option<StructureTable> {
+    let st = unsafe { crate::system_table().as_ref() };
+    let find = |guid| {
+        st.config_table()
+            .iter()
+            .find(|entry| entry.guid == guid)
+            .map(|entry| entry.address as *const u8)
+    };
+
+    // Entry points are physical addresses, which boot services identity-map
+    unsafe {
+        if let Some(entry) = find(SMBIOS3_GUID) {
+            let entry = slice::from_raw_parts(entry, 0x18);
+            if &entry[..5] == b"_SM3_" {
+                let max_size = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]);
+                let mut address = [0; 8];
+                address.copy_from_slice(&entry[16..24]);
+                let address = u64::from_le_bytes(address) as usize as *const u8;
+                return Some(StructureTable {
+                    data: slice::from_raw_parts(address, max_size as usize),
+                    version: (entry[7], entry[8]),
+                });
+            }
+        }
+        if let Some(entry) = find(SMBIOS_GUID) {
+            let entry = slice::from_raw_parts(entry, 0x1F);
+            if &entry[..4] == b"_SM_" && &entry[16..21] == b"_DMI_" {
+                let len = u16::from_le_bytes([entry[22], entry[23]]);
+                let address = u32::from_le_bytes([entry[24], entry[25], entry[26], entry[27]]);
+                return Some(StructureTable {
+                    data: slice::from_raw_parts(address as usize as *const u8, len.into()),
+                    version: (entry[6], entry[7]),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Finds the formatted area of the first structure of type `ty`
+fn find_structure(table: &StructureTable, ty: u8) -> Option<&'static [u8]> {
+    let data = table.data;
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let (structure_ty, len) = (data[offset], usize::from(data[offset + 1]));
+        if len < 4 || offset + len > data.len() {
+            return None;
+        }
+        if structure_ty == ty {
+            return Some(&data[offset..offset + len]);
+        }
+        if structure_ty == END_OF_TABLE {
+            return None;
+        }
+
+        // Skip the strings which follow the formatted area, up to a double NUL
+        let strings = &data[offset + len..];
+        let strings_len = strings.windows(2).position(|w| w == [0, 0])? + 2;
+        offset += len + strings_len;
+    }
+    None
+}
+
+/// Reads the UUID of the system from the SMBIOS system information.
+///
+/// The UUID is returned in the byte order of its canonical text form. Since
+/// version 2.6 of the SMBIOS specification, its first three fields are
+/// stored in little-endian order, these are swapped accordingly. Fails with
+/// `NOT_FOUND` if there is no SMBIOS or no system information, or if the
+/// UUID is not set.
+pub fn system_uuid() -> Result<[u8; 16]> {
+    let table = structure_table().ok_or(Status::NOT_FOUND)?;
+    let info = find_structure(&table, SYSTEM_INFORMATION).ok_or(Status::NOT_FOUND)?;
+
+    let mut uuid = [0; 16];
+    uuid.copy_from_slice(info.get(8..24).ok_or(Status::NOT_FOUND)?);
+    if uuid.iter().all(|&b| b == 0) || uuid.iter().all(|&b| b == 0xFF) {
+        return Err(Status::NOT_FOUND.into());
+    }
+    if table.version >= (2, 6) {
+        uuid[..4].reverse();
+        uuid[4..6].reverse();
+        uuid[6..8].reverse();
+    }
+
+    Ok(uuid.into())
+}
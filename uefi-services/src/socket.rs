@@ -0,0 +1,507 @@
+//! TCP and UDP sockets over IPv4.
+
+use core::ptr;
+use core::time::Duration;
+
+use uefi::prelude::*;
+use uefi::proto::network::tcp4::{
+    Tcp4, Tcp4AccessPoint, Tcp4CloseToken, Tcp4CompletionToken, Tcp4ConfigData, Tcp4FragmentData,
+    Tcp4IoToken, Tcp4Packet, Tcp4ReceiveData, Tcp4ServiceBinding, Tcp4TransmitData,
+};
+use uefi::proto::network::udp4::{
+    Udp4, Udp4CompletionToken, Udp4ConfigData, Udp4FragmentData, Udp4Packet, Udp4ServiceBinding,
+    Udp4SessionData, Udp4TransmitData,
+};
+use uefi::proto::network::Ipv4Address;
+use uefi::proto::Protocol;
+use uefi::table::boot::{BootServices, EventType, Tpl};
+use uefi::{Event, Result};
+
+/// Time to wait for the network interface to get an address, in microseconds
+const ADDRESS_TIMEOUT: usize = 5_000_000;
+
+/// Time to wait for a connection to be opened or closed, in microseconds
+const CONNECT_TIMEOUT: usize = 10_000_000;
+
+/// Delay between two checks of the status of an operation, in microseconds
+const POLL_INTERVAL: usize = 100;
+
+/// Time to live of the outgoing IPv4 headers
+const TIME_TO_LIVE: u8 = 64;
+
+fn boot_services() -> &'static BootServices {
+    unsafe { crate::system_table().as_ref().boot_services() }
+}
+
+/// Waits for an asynchronous operation to be over, and returns its status
+///
+/// `poll` is called to make progress. Fails with `TIMEOUT` if the operation
+/// is not over after `timeout` microseconds, in which case it must be
+/// cancelled by the caller.
+fn wait(status: *const Status, mut poll: impl FnMut(), timeout: Option<usize>) -> Result {
+    let bt = boot_services();
+    let mut elapsed = 0;
+    loop {
+        poll();
+        // The status is updated by the driver behind our back
+        let current = unsafe { ptr::read_volatile(status) };
+        if current != Status::NOT_READY {
+            return current.into();
+        }
+        if timeout.map_or(false, |timeout| elapsed >= timeout) {
+            return Err(Status::TIMEOUT.into());
+        }
+        bt.stall(POLL_INTERVAL);
+        elapsed += POLL_INTERVAL;
+    }
+}
+
+/// Checks whether cancelling the token of an operation which timed out
+/// failed, in which case the driver may still use the token
+///
+/// The caller must then abort all the pending operations of the child, as the
+/// token and its buffers are about to go away. Not finding the token means
+/// that the operation completed meanwhile, which is fine.
+fn cancel_failed(cancelled: Result) -> bool {
+    match cancelled {
+        Ok(_) => false,
+        Err(err) => err.status() != Status::NOT_FOUND,
+    }
+}
+
+/// Retries `configure` for as long as the network interface has no address
+fn configure_when_mapped(mut configure: impl FnMut() -> Result) -> Result {
+    let bt = boot_services();
+    let mut elapsed = 0;
+    loop {
+        match configure() {
+            Err(err) if err.status() == Status::NO_MAPPING && elapsed < ADDRESS_TIMEOUT => {
+                bt.stall(POLL_INTERVAL * 100);
+                elapsed += POLL_INTERVAL * 100;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Convert an optional timeout to microseconds
+fn timeout_micros(timeout: Option<Duration>) -> Option<usize> {
+    timeout.map(|timeout| timeout.as_micros() as usize)
+}
+
+/// Child handle created through a service binding protocol, along with the
+/// event used by its completion tokens
+struct Child {
+    service: Handle,
+    handle: Handle,
+    event: Event,
+}
+
+/// A UDP socket, as returned by `udp4_socket`.
+///
+/// The socket is closed when this is dropped.
+pub struct Udp4Socket {
+    child: Child,
+    udp: &'static mut Udp4,
+}
+
+impl Udp4Socket {
+    /// Sends a datagram to port `port` of address `addr`.
+    ///
+    /// Fails with `TIMEOUT` if the datagram could not be sent within a few
+    /// seconds. Should the driver then fail to cancel the operation, the
+    /// socket is reset and must not be used anymore.
+    pub fn send_to(&mut self, addr: Ipv4Address, port: u16, data: &[u8]) -> Result {
+        let session = Udp4SessionData {
+            destination_address: addr,
+            destination_port: port,
+            ..Default::default()
+        };
+        let tx_data = Udp4TransmitData {
+            udp_session_data: &session,
+            gateway_address: ptr::null(),
+            data_length: data.len() as u32,
+            fragment_count: 1,
+            fragment_table: [Udp4FragmentData {
+                fragment_length: data.len() as u32,
+                fragment_buffer: data.as_ptr() as *mut _,
+            }],
+        };
+        let mut token = Udp4CompletionToken {
+            event: self.child.event,
+            status: Status::NOT_READY,
+            packet: Udp4Packet { tx_data: &tx_data },
+        };
+
+        let udp = &mut *self.udp;
+        unsafe { udp.transmit(&mut token) }?.log();
+        let status = &token.status as *const Status;
+        let result = wait(
+            status,
+            || {
+                let _ = udp.poll();
+            },
+            Some(CONNECT_TIMEOUT),
+        );
+        if let Err(ref err) = result {
+            if err.status() == Status::TIMEOUT
+                && cancel_failed(unsafe { udp.cancel(Some(&mut token)) })
+            {
+                let _ = udp.configure(None);
+            }
+        }
+        result
+    }
+
+    /// Receives a datagram, waiting for at most `timeout` if it is not `None`.
+    ///
+    /// Returns the size of the datagram along with the address and port of its
+    /// sender. Datagrams larger than `buf` are truncated. Fails with `TIMEOUT`
+    /// if no datagram was received in time.
+    ///
+    /// Should the driver fail to cancel an operation which timed out, the
+    /// socket is reset and must not be used anymore.
+    pub fn recv_from(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, Ipv4Address, u16)> {
+        let mut token = Udp4CompletionToken {
+            event: self.child.event,
+            status: Status::NOT_READY,
+            packet: Udp4Packet {
+                rx_data: ptr::null_mut(),
+            },
+        };
+
+        let udp = &mut *self.udp;
+        unsafe { udp.receive(&mut token) }?.log();
+        let status = &token.status as *const Status;
+        let mut result = wait(
+            status,
+            || {
+                let _ = udp.poll();
+            },
+            timeout_micros(timeout),
+        );
+        if let Err(ref err) = result {
+            if err.status() == Status::TIMEOUT {
+                if cancel_failed(unsafe { udp.cancel(Some(&mut token)) }) {
+                    let _ = udp.configure(None);
+                }
+                // The datagram may have been received right before the
+                // cancellation, it must then be handed back to the driver
+                if unsafe { ptr::read_volatile(status) } == Status::SUCCESS {
+                    result = Status::SUCCESS.into();
+                }
+            }
+        }
+        if let Err(err) = result {
+            return Err(err);
+        }
+
+        let rx_data = unsafe { &*token.packet.rx_data };
+        let mut len = 0;
+        for fragment in rx_data.fragments() {
+            let size = (fragment.fragment_length as usize).min(buf.len() - len);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    fragment.fragment_buffer as *const u8,
+                    buf[len..].as_mut_ptr(),
+                    size,
+                );
+            }
+            len += size;
+        }
+        let session = rx_data.udp_session;
+
+        // Give the datagram back to the driver
+        boot_services().signal_event(rx_data.recycle_signal)?.log();
+        Ok((len, session.source_address, session.source_port).into())
+    }
+}
+
+impl Drop for Udp4Socket {
+    fn drop(&mut self) {
+        // This aborts the pending operations
+        let _ = self.udp.configure(None);
+        destroy_child::<Udp4ServiceBinding>(&self.child);
+    }
+}
+
+/// A TCP connection, as returned by `tcp4_connect`.
+///
+/// The connection is closed when this is dropped.
+pub struct Tcp4Socket {
+    child: Child,
+    tcp: &'static mut Tcp4,
+}
+
+impl Tcp4Socket {
+    /// Sends data over the connection.
+    ///
+    /// Fails with `TIMEOUT` if the data could not be sent within a few
+    /// seconds. Should the driver then fail to cancel the operation, the
+    /// connection is aborted.
+    pub fn send(&mut self, data: &[u8]) -> Result {
+        let tx_data = Tcp4TransmitData {
+            push: true,
+            urgent: false,
+            data_length: data.len() as u32,
+            fragment_count: 1,
+            fragment_table: [Tcp4FragmentData {
+                fragment_length: data.len() as u32,
+                fragment_buffer: data.as_ptr() as *mut _,
+            }],
+        };
+        let mut token = Tcp4IoToken {
+            completion_token: Tcp4CompletionToken {
+                event: self.child.event,
+                status: Status::NOT_READY,
+            },
+            packet: Tcp4Packet { tx_data: &tx_data },
+        };
+
+        // The TCP stack should give up on its own if the data is not
+        // acknowledged, but the token must not outlive this call either way
+        let tcp = &mut *self.tcp;
+        unsafe { tcp.transmit(&mut token) }?.log();
+        let status = &token.completion_token.status as *const Status;
+        let result = wait(
+            status,
+            || {
+                let _ = tcp.poll();
+            },
+            Some(CONNECT_TIMEOUT),
+        );
+        if let Err(ref err) = result {
+            if err.status() == Status::TIMEOUT
+                && cancel_failed(unsafe { tcp.cancel(Some(&mut token.completion_token)) })
+            {
+                let _ = tcp.configure(None);
+            }
+        }
+        result
+    }
+
+    /// Receives data from the connection, waiting for at most `timeout` if it
+    /// is not `None`.
+    ///
+    /// Returns the size of the received data, which is zero once the remote
+    /// side has closed the connection. Fails with `TIMEOUT` if no data was
+    /// received in time.
+    pub fn recv(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        let mut rx_data = Tcp4ReceiveData {
+            urgent_flag: false,
+            data_length: buf.len() as u32,
+            fragment_count: 1,
+            fragment_table: [Tcp4FragmentData {
+                fragment_length: buf.len() as u32,
+                fragment_buffer: buf.as_mut_ptr() as *mut _,
+            }],
+        };
+        let mut token = Tcp4IoToken {
+            completion_token: Tcp4CompletionToken {
+                event: self.child.event,
+                status: Status::NOT_READY,
+            },
+            packet: Tcp4Packet {
+                rx_data: &mut rx_data,
+            },
+        };
+
+        let tcp = &mut *self.tcp;
+        match unsafe { tcp.receive(&mut token) } {
+            Err(err) if err.status() == Status::CONNECTION_FIN => return Ok(0.into()),
+            result => result?.log(),
+        }
+        let status = &token.completion_token.status as *const Status;
+        match wait(
+            status,
+            || {
+                let _ = tcp.poll();
+            },
+            timeout_micros(timeout),
+        ) {
+            Ok(completion) => Ok(completion.map(|_| rx_data.data_length as usize)),
+            Err(err) if err.status() == Status::CONNECTION_FIN => Ok(0.into()),
+            Err(err) => {
+                if err.status() == Status::TIMEOUT
+                    && cancel_failed(unsafe { tcp.cancel(Some(&mut token.completion_token)) })
+                {
+                    let _ = tcp.configure(None);
+                }
+                // The data may have been received right before the cancellation
+                if unsafe { ptr::read_volatile(status) } == Status::SUCCESS {
+                    return Ok((rx_data.data_length as usize).into());
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for Tcp4Socket {
+    fn drop(&mut self) {
+        let mut token = Tcp4CloseToken {
+            completion_token: Tcp4CompletionToken {
+                event: self.child.event,
+                status: Status::NOT_READY,
+            },
+            abort_on_close: false,
+        };
+        let tcp = &mut *self.tcp;
+        if unsafe { tcp.close(&mut token) }.is_ok() {
+            let status = &token.completion_token.status as *const Status;
+            let _ = wait(
+                status,
+                || {
+                    let _ = tcp.poll();
+                },
+                Some(CONNECT_TIMEOUT),
+            );
+        }
+
+        // This aborts the connection if it could not be closed gracefully
+        let _ = tcp.configure(None);
+        destroy_child::<Tcp4ServiceBinding>(&self.child);
+    }
+}
+
+/// Service binding protocols of the network stack
+trait ServiceBinding: Protocol {
+    fn create(&mut self) -> Result<Handle>;
+    fn destroy(&mut self, child: Handle) -> Result;
+}
+
+impl ServiceBinding for Udp4ServiceBinding {
+    fn create(&mut self) -> Result<Handle> {
+        self.create_child()
+    }
+
+    fn destroy(&mut self, child: Handle) -> Result {
+        self.destroy_child(child)
+    }
+}
+
+impl ServiceBinding for Tcp4ServiceBinding {
+    fn create(&mut self) -> Result<Handle> {
+        self.create_child()
+    }
+
+    fn destroy(&mut self, child: Handle) -> Result {
+        self.destroy_child(child)
+    }
+}
+
+/// Creates a child handle through the service binding protocol `S`, and opens
+/// protocol `P` on it
+fn create_child<S: ServiceBinding, P: Protocol>() -> Result<(Child, &'static mut P)> {
+    let bt = boot_services();
+    let service = *bt
+        .find_handles::<S>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log()
+        .first()
+        .ok_or(Status::UNSUPPORTED)?;
+    let binding = bt.handle_protocol::<S>(service)?.log();
+    let binding = unsafe { &mut *binding.get() };
+    let handle = binding.create()?.log();
+
+    let opened = bt.handle_protocol::<P>(handle).and_then(|protocol| {
+        let event = unsafe { bt.create_event(EventType::empty(), Tpl::CALLBACK, None) }?;
+        Ok(event.map(|event| (event, protocol)))
+    });
+    match opened {
+        Ok(completion) => {
+            let (event, protocol) = completion.log();
+            let protocol = protocol.log();
+            let child = Child {
+                service,
+                handle,
+                event,
+            };
+            Ok((child, unsafe { &mut *protocol.get() }).into())
+        }
+        Err(err) => {
+            let _ = binding.destroy(handle);
+            Err(err)
+        }
+    }
+}
+
+/// Releases a child handle created by `create_child`
+fn destroy_child<S: ServiceBinding>(child: &Child) {
+    let bt = boot_services();
+    let _ = unsafe { bt.close_event(child.event) };
+    if let Ok(binding) = bt.handle_protocol::<S>(child.service) {
+        let binding = unsafe { &mut *binding.log().get() };
+        let _ = binding.destroy(child.handle);
+    }
+}
+
+/// Opens a UDP socket on a random local port.
+///
+/// The socket uses the address of the first network interface, waiting for
+/// a few seconds if it is still being configured. Fails with `UNSUPPORTED`
+/// if the firmware does not provide a UDP4 network stack.
+pub fn udp4_socket() -> Result<Udp4Socket> {
+    let (child, udp) = create_child::<Udp4ServiceBinding, Udp4>()?.log();
+    let socket = Udp4Socket { child, udp };
+
+    let config = Udp4ConfigData {
+        time_to_live: TIME_TO_LIVE,
+        use_default_address: true,
+        ..Default::default()
+    };
+    let udp = &mut *socket.udp;
+    configure_when_mapped(|| udp.configure(Some(&config)))?.log();
+    Ok(socket.into())
+}
+
+/// Opens a TCP connection to port `port` of address `addr`.
+///
+/// The connection uses the address of the first network interface, waiting
+/// for a few seconds if it is still being configured. Fails with
+/// `UNSUPPORTED` if the firmware does not provide a TCP4 network stack, and
+/// with `TIMEOUT` if the connection could not be opened in time.
+pub fn tcp4_connect(addr: Ipv4Address, port: u16) -> Result<Tcp4Socket> {
+    let (child, tcp) = create_child::<Tcp4ServiceBinding, Tcp4>()?.log();
+    let socket = Tcp4Socket { child, tcp };
+
+    let config = Tcp4ConfigData {
+        type_of_service: 0,
+        time_to_live: TIME_TO_LIVE,
+        access_point: Tcp4AccessPoint {
+            use_default_address: true,
+            remote_address: addr,
+            remote_port: port,
+            active_flag: true,
+            ..Default::default()
+        },
+        control_option: ptr::null(),
+    };
+    let tcp = &mut *socket.tcp;
+    configure_when_mapped(|| tcp.configure(Some(&config)))?.log();
+
+    let mut token = Tcp4CompletionToken {
+        event: socket.child.event,
+        status: Status::NOT_READY,
+    };
+    unsafe { tcp.connect(&mut token) }?.log();
+    let status = &token.status as *const Status;
+    if let Err(err) = wait(
+        status,
+        || {
+            let _ = tcp.poll();
+        },
+        Some(CONNECT_TIMEOUT),
+    ) {
+        if err.status() == Status::TIMEOUT && cancel_failed(unsafe { tcp.cancel(Some(&mut token)) })
+        {
+            let _ = tcp.configure(None);
+        }
+        return Err(err);
+    }
+    Ok(socket.into())
+}
@@ -0,0 +1,277 @@
+//! Raw access to block devices.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::{char, ptr};
+
+use uefi::prelude::*;
+use uefi::proto::media::block::{BlockIO, Lba};
+use uefi::proto::media::ramdisk::{RamDisk, VIRTUAL_DISK_GUID};
+use uefi::table::boot::{AllocateType, MemoryType};
+use uefi::{Guid, Handle, Result};
+
+use crate::crc::crc32_any;
+use crate::memory::PAGE_SIZE;
+
+/// Description of a block device, as returned by `block_devices`.
+#[derive(Debug, Copy, Clone)]
+pub struct BlockDeviceInfo {
+    /// Handle which supports the Block I/O protocol.
+    pub handle: Handle,
+    /// Current media ID of the device.
+    pub media_id: u32,
+    /// Size of a block, in bytes.
+    pub block_size: u32,
+    /// Address of the last block of the device.
+    pub last_block: Lba,
+    /// Whether the media can be removed from the device.
+    pub removable: bool,
+    /// Whether the device is a partition of a larger device.
+    pub logical_partition: bool,
+    /// Whether the device is read-only.
+    pub read_only: bool,
+}
+
+/// Enumerates the devices which support the Block I/O protocol.
+///
+/// Devices whose media is not present are skipped.
+pub fn block_devices() -> Result<Vec<BlockDeviceInfo>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let handles = bt.find_handles::<BlockIO>()?.log();
+    let mut devices = Vec::with_capacity(handles.len());
+    for handle in handles {
+        let block_io = bt.handle_protocol::<BlockIO>(handle)?.log();
+        let media = unsafe { (*block_io.get()).media() };
+        if !media.is_media_preset() {
+            continue;
+        }
+
+        devices.push(BlockDeviceInfo {
+            handle,
+            media_id: media.media_id(),
+            block_size: media.block_size(),
+            last_block: media.last_block(),
+            removable: media.is_removable_media(),
+            logical_partition: media.is_logical_partition(),
+            read_only: media.is_read_only(),
+        });
+    }
+
+    Ok(devices.into())
+}
+
+/// Reads blocks from a block device, starting at block `lba`.
+///
+/// The length of `buf` must be a multiple of the device's block size,
+/// otherwise `BAD_BUFFER_SIZE` is returned.
+///
+/// If the device has no media, this fails with `NO_MEDIA`. If the media was
+/// changed since the device was last accessed, this fails with
+/// `MEDIA_CHANGED`; calling this function again will then read from the new
+/// media.
+pub fn read_blocks(handle: Handle, lba: Lba, buf: &mut [u8]) -> Result {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    let block_io = bt.handle_protocol::<BlockIO>(handle)?.log();
+    let block_io = unsafe { &*block_io.get() };
+    let media = block_io.media();
+    if !media.is_media_preset() {
+        return Err(Status::NO_MEDIA.into());
+    }
+
+    let block_size = media.block_size() as usize;
+    if block_size == 0 || buf.len() % block_size != 0 {
+        return Err(Status::BAD_BUFFER_SIZE.into());
+    }
+
+    block_io.read_blocks(media.media_id(), lba, buf)
+}
+
+/// Signature of a GPT header
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// Size of the GPT header fields defined by the specification
+const GPT_HEADER_MIN_SIZE: usize = 92;
+
+/// Offset of the CRC32 field in the GPT header
+const GPT_HEADER_CRC_OFFSET: usize = 16;
+
+/// Minimal size of a GPT partition entry
+const GPT_ENTRY_MIN_SIZE: usize = 128;
+
+/// Maximal size of the GPT partition entry array accepted by `read_gpt`
+///
+/// The default array is 16 KiB, this leaves plenty of room while keeping a
+/// corrupted header from requesting a huge allocation.
+const GPT_ENTRIES_MAX_SIZE: usize = 1024 * 1024;
+
+/// Number of UCS-2 characters in the name of a GPT partition
+const GPT_NAME_LEN: usize = 36;
+
+/// Description of a GPT partition, as returned by `read_gpt`.
+#[derive(Debug, Clone)]
+pub struct GptPartition {
+    /// GUID identifying the purpose of the partition.
+    pub type_guid: Guid,
+    /// GUID identifying this partition.
+    pub unique_guid: Guid,
+    /// Address of the first block of the partition.
+    pub start_lba: Lba,
+    /// Address of the last block of the partition, inclusive.
+    pub end_lba: Lba,
+    /// Attribute bits of the partition.
+    pub attributes: u64,
+    /// Human-readable name of the partition.
+    pub name: String,
+}
+
+/// Read a little-endian `u32` at `offset` in `buf`
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read a little-endian `u64` at `offset` in `buf`
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Read a GUID at `offset` in `buf`
+fn read_guid(buf: &[u8], offset: usize) -> Guid {
+    assert!(offset + 16 <= buf.len());
+    // GPT stores GUIDs in the same mixed-endian layout as UEFI does in memory
+    unsafe { ptr::read_unaligned(buf.as_ptr().add(offset) as *const Guid) }
+}
+
+/// Reads the GUID Partition Table of a block device.
+///
+/// The primary GPT header is read from block 1, and its signature and CRC32
+/// are checked, as well as the CRC32 of the partition entry array. Fails with
+/// `NOT_FOUND` if the device has no GPT, `CRC_ERROR` if a checksum does not
+/// match, `NO_MEDIA` if the device reports no block size and
+/// `VOLUME_CORRUPTED` if the header is malformed, including when the
+/// partition entry array is larger than 1 MiB. The checksums are computed in
+/// software if the firmware does not provide `CalculateCrc32`.
+///
+/// Unused entries are skipped.
+pub fn read_gpt(handle: Handle) -> Result<Vec<GptPartition>> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let block_io = bt.handle_protocol::<BlockIO>(handle)?.log();
+    let block_size = unsafe { (*block_io.get()).media() }.block_size() as usize;
+    if block_size == 0 {
+        return Err(Status::NO_MEDIA.into());
+    }
+
+    // Read and check the header
+    let mut header = vec![0; block_size];
+    read_blocks(handle, 1, &mut header)?.log();
+    if &header[..GPT_SIGNATURE.len()] != GPT_SIGNATURE {
+        return Err(Status::NOT_FOUND.into());
+    }
+    let header_size = read_u32(&header, 12) as usize;
+    if header_size < GPT_HEADER_MIN_SIZE || header_size > block_size {
+        return Err(Status::VOLUME_CORRUPTED.into());
+    }
+    let header_crc = read_u32(&header, GPT_HEADER_CRC_OFFSET);
+    header[GPT_HEADER_CRC_OFFSET..GPT_HEADER_CRC_OFFSET + 4].fill(0);
+    if crc32_any(&header[..header_size]) != header_crc {
+        return Err(Status::CRC_ERROR.into());
+    }
+
+    // Read and check the partition entry array
+    let entries_lba = read_u64(&header, 72);
+    let entry_count = read_u32(&header, 80) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    let entries_crc = read_u32(&header, 88);
+    if entry_size < GPT_ENTRY_MIN_SIZE || entry_size % 8 != 0 {
+        return Err(Status::VOLUME_CORRUPTED.into());
+    }
+    let entries_size = entry_count
+        .checked_mul(entry_size)
+        .filter(|&size| size <= GPT_ENTRIES_MAX_SIZE)
+        .ok_or(Status::VOLUME_CORRUPTED)?;
+    let blocks = (entries_size + block_size - 1) / block_size;
+    let mut entries = vec![0; blocks * block_size];
+    read_blocks(handle, entries_lba, &mut entries)?.log();
+    let entries = &entries[..entries_size];
+    if crc32_any(entries) != entries_crc {
+        return Err(Status::CRC_ERROR.into());
+    }
+
+    let zero_guid = Guid::from_values(0, 0, 0, 0, [0; 6]);
+    let partitions: Vec<_> = entries
+        .chunks_exact(entry_size)
+        .map(|entry| (read_guid(entry, 0), entry))
+        .filter(|(type_guid, _)| *type_guid != zero_guid)
+        .map(|(type_guid, entry)| {
+            let name = entry[56..56 + 2 * GPT_NAME_LEN]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0);
+            GptPartition {
+                type_guid,
+                unique_guid: read_guid(entry, 16),
+                start_lba: read_u64(entry, 32),
+                end_lba: read_u64(entry, 40),
+                attributes: read_u64(entry, 48),
+                name: char::decode_utf16(name)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(partitions.into())
+}
+
+/// Registers a copy of `data` as a virtual RAM disk.
+///
+/// The contents are copied to pages which are never freed, so the disk stays
+/// valid for as long as boot services are running. Returns the handle of the
+/// new Block I/O device, on which the firmware will typically connect a file
+/// system driver if the image contains one.
+///
+/// Fails with `UNSUPPORTED` if the firmware does not implement the RAM disk
+/// protocol.
+pub fn install_ramdisk(data: &[u8]) -> Result<Handle> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+
+    if data.is_empty() {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let ramdisk = bt
+        .locate_protocol::<RamDisk>()
+        .map_err(|_| Status::UNSUPPORTED)?
+        .log();
+    let ramdisk = unsafe { &*ramdisk.get() };
+
+    let pages = (data.len() + PAGE_SIZE as usize - 1) / PAGE_SIZE as usize;
+    let base = bt
+        .allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)?
+        .log();
+    unsafe {
+        ptr::copy_nonoverlapping(data.as_ptr(), base as *mut u8, data.len());
+    }
+
+    let device_path =
+        match unsafe { ramdisk.register(base, data.len() as u64, &VIRTUAL_DISK_GUID, None) } {
+            Ok(device_path) => device_path.log(),
+            Err(e) => {
+                let _ = bt.free_pages(base, pages);
+                return Err(e);
+            }
+        };
+
+    let device_path = unsafe { &mut *device_path };
+    let handle = bt.locate_device_path::<BlockIO>(device_path);
+    if handle.is_err() {
+        // Without a handle the disk is of no use to the caller
+        if ramdisk.unregister(device_path).is_ok() {
+            let _ = bt.free_pages(base, pages);
+        }
+    }
+    let _ = bt.free_pool(device_path as *mut _ as *mut u8);
+    handle
+}
@@ -0,0 +1,240 @@
+//! Time keeping helpers.
+
+use core::time::Duration;
+
+#[cfg(not(feature = "no_panic_handler"))]
+use uefi::table::boot::BootServices;
+use uefi::table::runtime::Time;
+use uefi::{Result, ResultExt};
+
+/// Number of days between 1970-01-01 and the given date of the proleptic
+/// Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Shift the year to start in March, so that leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Seconds elapsed between 1970-01-01 00:00:00 and `time`, ignoring time zones
+fn seconds_since_epoch(time: &Time) -> i64 {
+    let days = days_from_civil(
+        i64::from(time.year()),
+        u32::from(time.month()),
+        u32::from(time.day()),
+    );
+    days * 86_400
+        + i64::from(time.hour()) * 3_600
+        + i64::from(time.minute()) * 60
+        + i64::from(time.second())
+}
+
+/// Time elapsed between 1970-01-01 00:00:00 and `time`, ignoring time zones
+#[cfg(not(feature = "no_logging"))]
+pub(crate) fn duration_since_epoch(time: &Time) -> Duration {
+    Duration::new(seconds_since_epoch(time) as u64, time.nanosecond())
+}
+
+/// Converts a time, as reported by the real time clock, to a Unix timestamp.
+///
+/// The time zone is taken into account, as an offset of the local time from
+/// UTC. If it is unspecified, the time is assumed to be in UTC.
+pub fn unix_timestamp(time: &Time) -> i64 {
+    let offset = time
+        .time_zone()
+        .map_or(0, |minutes| i64::from(minutes) * 60);
+    seconds_since_epoch(time) - offset
+}
+
+/// Reads the real time clock as a Unix timestamp.
+///
+/// See `unix_timestamp` for how the time zone of the clock is handled.
+pub fn unix_time() -> Result<i64> {
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+    rt.get_time().map_inner(|time| unix_timestamp(&time))
+}
+
+/// Clock of the global logger, based on the real time clock
+#[cfg(not(feature = "no_logging"))]
+pub(crate) fn logger_clock() -> Duration {
+    unsafe { crate::SYSTEM_TABLE.as_ref() }
+        .and_then(|st| st.runtime_services().get_time().ok())
+        .map(|time| duration_since_epoch(&time.log()))
+        .unwrap_or_default()
+}
+
+/// Reads the processor's timestamp counter
+///
+/// The frequency of the counter is only known on x86_64, see `tsc_frequency`,
+/// elsewhere the values are only useful for comparisons. Architectures without
+/// a supported counter always read 0.
+pub(crate) fn ticks() -> u64 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            unsafe { core::arch::x86_64::_rdtsc() }
+        } else if #[cfg(target_arch = "aarch64")] {
+            let ticks: u64;
+            unsafe {
+                asm!("mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+            }
+            ticks
+        } else {
+            0
+        }
+    }
+}
+
+/// Frequency of the timestamp counter in Hz, 0 until `tsc_frequency` has
+/// determined it
+#[cfg(target_arch = "x86_64")]
+static mut TSC_FREQUENCY: u64 = 0;
+
+/// Determines the frequency of the timestamp counter, in Hz.
+///
+/// Processors which enumerate it in CPUID leaf 0x15 report it as a ratio of
+/// their crystal clock. On the others, and in virtual machines which leave
+/// the leaf empty, the counter is measured against the boot services' `stall`
+/// over 10 ms. The result is cached for later calls.
+///
+/// Fails with `UNSUPPORTED` if the frequency has to be measured after boot
+/// services have been exited, and with `DEVICE_ERROR` if the counter does not
+/// advance.
+#[cfg(target_arch = "x86_64")]
+pub fn tsc_frequency() -> Result<u64> {
+    use core::arch::x86_64::__cpuid;
+    use uefi::Status;
+
+    const STALL_MICROSECONDS: usize = 10_000;
+
+    let cached = unsafe { TSC_FREQUENCY };
+    if cached != 0 {
+        return Ok(cached.into());
+    }
+
+    let mut frequency = 0;
+    if unsafe { __cpuid(0) }.eax >= 0x15 {
+        // The leaf gives the crystal clock frequency and the TSC/crystal ratio
+        let leaf = unsafe { __cpuid(0x15) };
+        if leaf.eax != 0 && leaf.ebx != 0 && leaf.ecx != 0 {
+            frequency = u64::from(leaf.ecx) * u64::from(leaf.ebx) / u64::from(leaf.eax);
+        }
+    }
+
+    if frequency == 0 {
+        let st = unsafe { crate::SYSTEM_TABLE.as_ref() }.ok_or(Status::UNSUPPORTED)?;
+        let start = ticks();
+        st.boot_services().stall(STALL_MICROSECONDS);
+        let elapsed = ticks().wrapping_sub(start);
+        frequency = elapsed * (1_000_000 / STALL_MICROSECONDS as u64);
+    }
+
+    if frequency == 0 {
+        return Err(Status::DEVICE_ERROR.into());
+    }
+    unsafe {
+        TSC_FREQUENCY = frequency;
+    }
+    Ok(frequency.into())
+}
+
+/// Timestamp counter value read when the library was initialized
+static mut INIT_TICKS: u64 = 0;
+
+/// Timestamp counter ticks elapsed between initialization and exit from boot
+/// services, once the latter has happened
+static mut BOOT_SERVICES_DURATION_TICKS: Option<u64> = None;
+
+/// Record the start of the boot services phase
+pub(crate) fn record_init() {
+    unsafe {
+        INIT_TICKS = ticks();
+    }
+}
+
+/// Record the end of the boot services phase
+///
+/// This is called from the exit boot services notification, where logging
+/// may not work anymore, so the duration is only stored.
+pub(crate) fn record_exit_boot_services() {
+    unsafe {
+        BOOT_SERVICES_DURATION_TICKS = Some(ticks().wrapping_sub(INIT_TICKS));
+    }
+}
+
+/// Query how long the boot services phase lasted.
+///
+/// This is the number of timestamp counter ticks elapsed between `init` and
+/// the exit from boot services, which makes for a simple boot time metric.
+/// On x86_64, `tsc_frequency` converts it to a duration. Elsewhere the
+/// counter's frequency is not known, so this is mostly useful to compare
+/// boots of the same machine.
+///
+/// Returns `None` until boot services have been exited.
+pub fn boot_services_duration_ticks() -> Option<u64> {
+    unsafe { BOOT_SERVICES_DURATION_TICKS }
+}
+
+/// Number of iterations of `busy_loop` per second, as calibrated by
+/// `calibrate_busy_loop`
+///
+/// The default matches a loop of 300 million iterations lasting 10 seconds.
+#[cfg(not(feature = "no_panic_handler"))]
+static mut BUSY_LOOP_ITERATIONS_PER_SECOND: u64 = 30_000_000;
+
+/// Spin for `iterations` rounds of a loop the compiler cannot optimize out
+#[cfg(not(feature = "no_panic_handler"))]
+fn busy_loop(iterations: u64) {
+    let mut dummy = 0u64;
+    for i in 0..iterations {
+        unsafe {
+            core::ptr::write_volatile(&mut dummy, i);
+        }
+    }
+}
+
+/// Measure the speed of `busy_loop`, using the boot services' `stall`
+///
+/// The timestamp counter is used as a common time base, so this keeps the
+/// default if the architecture has no supported counter.
+#[cfg(not(feature = "no_panic_handler"))]
+pub(crate) fn calibrate_busy_loop(bt: &BootServices) {
+    const STALL_MICROSECONDS: usize = 10_000;
+    const ITERATIONS: u64 = 1_000_000;
+
+    let start = ticks();
+    bt.stall(STALL_MICROSECONDS);
+    let ticks_per_stall = ticks().wrapping_sub(start);
+
+    let start = ticks();
+    busy_loop(ITERATIONS);
+    let ticks_per_loop = ticks().wrapping_sub(start);
+
+    if ticks_per_stall == 0 || ticks_per_loop == 0 {
+        return;
+    }
+    let stalls_per_second = 1_000_000 / STALL_MICROSECONDS as u128;
+    let iterations_per_second =
+        u128::from(ITERATIONS) * u128::from(ticks_per_stall) * stalls_per_second
+            / u128::from(ticks_per_loop);
+    if iterations_per_second != 0 {
+        unsafe {
+            BUSY_LOOP_ITERATIONS_PER_SECOND =
+                iterations_per_second.min(u128::from(u64::MAX)) as u64;
+        }
+    }
+}
+
+/// Waits for about `duration` without using boot services
+///
+/// This relies on the calibration done during initialization, or on a rough
+/// estimate if it did not happen.
+#[cfg(not(feature = "no_panic_handler"))]
+pub(crate) fn busy_wait(duration: Duration) {
+    let per_second = u128::from(unsafe { BUSY_LOOP_ITERATIONS_PER_SECOND });
+    let iterations = duration.as_micros() * per_second / 1_000_000;
+    busy_loop(iterations.min(u128::from(u64::MAX)) as u64);
+}
@@ -0,0 +1,218 @@
+//! UEFI variable helpers.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use uefi::prelude::*;
+use uefi::table::runtime::{VariableAttributes, GLOBAL_VARIABLE};
+use uefi::{CStr16, Guid, Result, ResultExt};
+
+use crate::fs::{cstr16_to_string, ucs2_with_nul};
+
+/// Builder for the attributes of a variable.
+///
+/// The building blocks match the usual combinations of attributes. Notably,
+/// runtime access implies boot services access, which UEFI requires.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct VariableAttributesBuilder(VariableAttributes);
+
+impl VariableAttributesBuilder {
+    /// Starts from a volatile variable without any access.
+    pub fn new() -> Self {
+        Self(VariableAttributes::empty())
+    }
+
+    /// Makes the variable persist across resets.
+    pub fn nv(self) -> Self {
+        Self(self.0 | VariableAttributes::NON_VOLATILE)
+    }
+
+    /// Makes the variable accessible while boot services are running.
+    pub fn boot(self) -> Self {
+        Self(self.0 | VariableAttributes::BOOTSERVICE_ACCESS)
+    }
+
+    /// Makes the variable accessible after boot services have been exited.
+    pub fn runtime(self) -> Self {
+        Self(self.boot().0 | VariableAttributes::RUNTIME_ACCESS)
+    }
+
+    /// Requires writes to the variable to be signed, with a time-based
+    /// authentication descriptor prepended to the data.
+    pub fn auth(self) -> Self {
+        Self(self.0 | VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS)
+    }
+
+    /// Returns the attributes built so far.
+    pub fn build(self) -> VariableAttributes {
+        self.0
+    }
+}
+
+impl Default for VariableAttributesBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<VariableAttributesBuilder> for VariableAttributes {
+    fn from(builder: VariableAttributesBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Writes a variable.
+///
+/// Passing empty data deletes the variable. Fails with `INVALID_PARAMETER`
+/// if the attributes of a non-empty variable lack boot services access,
+/// which is the usual way of forgetting to set any access.
+pub fn set_variable_typed(
+    name: &str,
+    vendor: &Guid,
+    attributes: impl Into<VariableAttributes>,
+    data: &[u8],
+) -> Result {
+    let attributes = attributes.into();
+    if !data.is_empty() && !attributes.contains(VariableAttributes::BOOTSERVICE_ACCESS) {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let name = ucs2_with_nul(name)?.log();
+    let name = CStr16::from_u16_with_nul(&name).map_err(|_| Status::INVALID_PARAMETER)?;
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+    unsafe { rt.set_variable(name, vendor, attributes, data) }
+}
+
+/// Reads a variable, along with its attributes.
+///
+/// Fails with `NOT_FOUND` if the variable does not exist.
+pub fn get_variable_typed(name: &str, vendor: &Guid) -> Result<(Vec<u8>, VariableAttributes)> {
+    let name = ucs2_with_nul(name)?.log();
+    let name = CStr16::from_u16_with_nul(&name).map_err(|_| Status::INVALID_PARAMETER)?;
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+
+    let mut buf = vec![0; rt.get_variable_size(name, vendor)?.log()];
+    rt.get_variable(name, vendor, &mut buf)
+        .map_err(|err| err.status().into())
+        .map_inner(|(data, attributes)| (data.to_vec(), attributes))
+}
+
+/// Attributes of the boot manager's variables
+fn boot_variable_attributes() -> VariableAttributes {
+    VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS
+}
+
+/// Write a global variable
+fn set_global_variable(name: &str, attributes: VariableAttributes, data: &[u8]) -> Result {
+    let name = ucs2_with_nul(name)?.log();
+    let name = CStr16::from_u16_with_nul(&name).map_err(|_| Status::INVALID_PARAMETER)?;
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+    unsafe { rt.set_variable(name, &GLOBAL_VARIABLE, attributes, data) }
+}
+
+/// Read a global variable into `buf`, returning the size of its data
+pub(crate) fn get_global_variable(name: &str, buf: &mut [u8]) -> Result<usize> {
+    let name = ucs2_with_nul(name)?.log();
+    let name = CStr16::from_u16_with_nul(&name).map_err(|_| Status::INVALID_PARAMETER)?;
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+    rt.get_variable(name, &GLOBAL_VARIABLE, buf)
+        .map_err(|err| err.status().into())
+        .map_inner(|(data, _attributes)| data.len())
+}
+
+/// Select the boot option to use on the next boot only.
+///
+/// This writes the `BootNext` variable, so that the boot manager tries the
+/// `Boot####` option with the given number once, before going back to the
+/// usual `BootOrder`. Resetting the system afterwards reboots into it.
+pub fn set_boot_next(option: u16) -> Result {
+    set_global_variable(
+        "BootNext",
+        boot_variable_attributes(),
+        &option.to_le_bytes(),
+    )
+}
+
+/// Cancel the effect of `set_boot_next`.
+///
+/// This deletes the `BootNext` variable, it is not an error if it was not set.
+pub fn clear_boot_next() -> Result {
+    match set_global_variable("BootNext", VariableAttributes::empty(), &[]) {
+        Err(err) if err.status() == Status::NOT_FOUND => Status::SUCCESS.into(),
+        result => result,
+    }
+}
+
+/// Set the number of seconds the boot manager waits before booting the
+/// default option.
+///
+/// This writes the `Timeout` variable. The value `0xFFFF` makes the boot
+/// manager wait for user input.
+pub fn set_boot_timeout(seconds: u16) -> Result {
+    set_global_variable(
+        "Timeout",
+        boot_variable_attributes(),
+        &seconds.to_le_bytes(),
+    )
+}
+
+/// Query the number of seconds the boot manager waits before booting the
+/// default option.
+///
+/// Returns `None` if the `Timeout` variable is not set, in which case the
+/// boot manager uses its own default. Fails with `VOLUME_CORRUPTED` if the
+/// variable does not hold a 16-bit value.
+pub fn boot_timeout() -> Result<Option<u16>> {
+    let mut buf = [0; 2];
+    match get_global_variable("Timeout", &mut buf) {
+        Ok(completion) => match completion.log() {
+            2 => Ok(Some(u16::from_le_bytes(buf)).into()),
+            _ => Err(Status::VOLUME_CORRUPTED.into()),
+        },
+        Err(err) if err.status() == Status::NOT_FOUND => Ok(None.into()),
+        Err(err) if err.status() == Status::BUFFER_TOO_SMALL => {
+            Err(Status::VOLUME_CORRUPTED.into())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Lists the names and vendor GUIDs of all variables.
+///
+/// Variables which are only accessible at runtime are included after boot
+/// services have been exited, but not before.
+pub fn enumerate_variables() -> Result<Vec<(String, Guid)>> {
+    let rt = unsafe { crate::system_table().as_ref().runtime_services() };
+
+    // An empty name starts the enumeration
+    let mut name = vec![0u16; 64];
+    let mut vendor = Guid::from_values(0, 0, 0, 0, [0; 6]);
+    let mut variables = Vec::new();
+    loop {
+        match rt.get_next_variable_name(&mut name, &mut vendor) {
+            Ok(completion) => completion.log(),
+            Err(err) if err.status() == Status::NOT_FOUND => break,
+            Err(err) => match *err.data() {
+                // The name of the previous variable must be kept while growing
+                Some(len) => {
+                    name.resize(len, 0);
+                    continue;
+                }
+                None => return Err(err.status().into()),
+            },
+        }
+
+        let len = name
+            .iter()
+            .position(|&c| c == 0)
+            .ok_or(Status::VOLUME_CORRUPTED)?;
+        let current =
+            CStr16::from_u16_with_nul(&name[..=len]).map_err(|_| Status::VOLUME_CORRUPTED)?;
+        variables.push((cstr16_to_string(current), vendor));
+    }
+
+    Ok(variables.into())
+}
@@ -0,0 +1,57 @@
+//! Watchdog timer helpers.
+
+use uefi::prelude::*;
+use uefi::Result;
+
+use crate::fs::ucs2_with_nul;
+
+/// Timeout of the watchdog armed by the boot manager, in seconds
+const DEFAULT_WATCHDOG_TIMEOUT: usize = 5 * 60;
+
+/// Watchdog code used when no code was requested, the first one available
+/// to applications
+const DEFAULT_WATCHDOG_CODE: u64 = 0x10000;
+
+/// Arms the watchdog timer to reset the system after `seconds`.
+///
+/// If the watchdog fires, the firmware logs `code` and `data`, which can be
+/// used to tell which operation took too long. Codes up to 0xFFFF are
+/// reserved for the firmware, and fail with `INVALID_PARAMETER`, as does data
+/// which cannot be encoded in UCS-2. Passing 0 seconds disables the watchdog.
+pub fn arm_watchdog(seconds: usize, code: u64, data: &str) -> Result {
+    if code <= 0xFFFF {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+    let mut data = ucs2_with_nul(data)?.log();
+
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    bt.set_watchdog_timer(seconds, code, Some(&mut data))
+}
+
+/// Guard re-arming the watchdog timer when dropped, as returned by
+/// `watchdog_pause`.
+///
+/// The guard must not outlive boot services, since the watchdog timer is
+/// a boot service.
+#[must_use = "the watchdog is re-armed as soon as the guard is dropped"]
+pub struct WatchdogGuard {
+    _private: (),
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        let bt = unsafe { crate::system_table().as_ref().boot_services() };
+        let _ = bt.set_watchdog_timer(DEFAULT_WATCHDOG_TIMEOUT, DEFAULT_WATCHDOG_CODE, None);
+    }
+}
+
+/// Disables the watchdog timer until the returned guard is dropped.
+///
+/// The watchdog is then re-armed with the five minutes timeout which the
+/// boot manager sets before starting an image. This is useful around long
+/// operations such as network downloads.
+pub fn watchdog_pause() -> WatchdogGuard {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let _ = bt.set_watchdog_timer(0, DEFAULT_WATCHDOG_CODE, None);
+    WatchdogGuard { _private: () }
+}
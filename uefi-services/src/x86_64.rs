@@ -0,0 +1,438 @@
+//! Helpers specific to the x86_64 architecture.
+
+use core::ptr;
+
+use uefi::prelude::*;
+use uefi::table::boot::{AllocateType, MemoryType};
+#[cfg(feature = "cmos_rtc")]
+use uefi::table::runtime::{Daylight, Time};
+use uefi::Result;
+
+/// Size of a page table, and of the pages it is made of
+const PAGE_SIZE: u64 = 4096;
+
+/// Number of entries in a page table
+const ENTRIES_PER_TABLE: u64 = 512;
+
+/// Size of the memory mapped by a page directory entry
+const PD_ENTRY_SIZE: u64 = 2 << 20;
+
+/// Size of the memory mapped by a page directory pointer table entry
+const PDPT_ENTRY_SIZE: u64 = PD_ENTRY_SIZE * ENTRIES_PER_TABLE;
+
+/// Size of the memory mapped by a PML4 entry
+const PML4_ENTRY_SIZE: u64 = PDPT_ENTRY_SIZE * ENTRIES_PER_TABLE;
+
+/// Page table entry flag: the entry is valid
+const PRESENT: u64 = 1;
+
+/// Page table entry flag: the memory is writable
+const WRITABLE: u64 = 1 << 1;
+
+/// Page directory entry flag: the entry maps a 2 MiB page
+const HUGE_PAGE: u64 = 1 << 7;
+
+/// Builds 4-level page tables identity-mapping the physical memory `0..max_phys`.
+///
+/// The memory is mapped read-write with 2 MiB pages, `max_phys` is rounded up
+/// accordingly. Returns the physical address of the PML4, which can be loaded
+/// into CR3. Fails with `INVALID_PARAMETER` if `max_phys` is zero or above
+/// the 256 TiB which 4-level paging can map.
+///
+/// The page tables are allocated as `LOADER_DATA`, so a kernel which takes
+/// over the memory map must keep them around for as long as it uses them.
+pub fn build_identity_map(max_phys: u64) -> Result<u64> {
+    if max_phys == 0 || max_phys > PML4_ENTRY_SIZE * ENTRIES_PER_TABLE {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let div_ceil = |size: u64, unit: u64| (size + unit - 1) / unit;
+    let pd_count = div_ceil(max_phys, PDPT_ENTRY_SIZE);
+    let pdpt_count = div_ceil(max_phys, PML4_ENTRY_SIZE);
+    let huge_page_count = div_ceil(max_phys, PD_ENTRY_SIZE);
+
+    // Allocate all the tables at once, in the order PML4, PDPTs, PDs
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let table_count = 1 + pdpt_count + pd_count;
+    let pml4 = bt
+        .allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LOADER_DATA,
+            table_count as usize,
+        )?
+        .log();
+    let pdpts = pml4 + PAGE_SIZE;
+    let pds = pdpts + pdpt_count * PAGE_SIZE;
+
+    // Boot services identity-map memory, so the tables can be written directly
+    unsafe {
+        ptr::write_bytes(pml4 as *mut u8, 0, (table_count * PAGE_SIZE) as usize);
+        let entry = |table: u64, index: u64| (table as *mut u64).add(index as usize);
+
+        for i in 0..pdpt_count {
+            *entry(pml4, i) = (pdpts + i * PAGE_SIZE) | PRESENT | WRITABLE;
+        }
+        // The PDPTs and PDs are contiguous, so they can be indexed globally
+        for i in 0..pd_count {
+            *entry(pdpts, i) = (pds + i * PAGE_SIZE) | PRESENT | WRITABLE;
+        }
+        for i in 0..huge_page_count {
+            *entry(pds, i) = (i * PD_ENTRY_SIZE) | PRESENT | WRITABLE | HUGE_PAGE;
+        }
+    }
+
+    Ok(pml4.into())
+}
+
+/// Flat 64-bit code segment descriptor: present, ring 0, executable, long mode
+const GDT_CODE_DESCRIPTOR: u64 = 0x00AF_9A00_0000_FFFF;
+
+/// Flat data segment descriptor: present, ring 0, writable
+const GDT_DATA_DESCRIPTOR: u64 = 0x00CF_9200_0000_FFFF;
+
+/// Selector of the code segment of the minimal GDT
+pub const GDT_CODE_SELECTOR: u16 = 0x08;
+
+/// Selector of the data segment of the minimal GDT
+pub const GDT_DATA_SELECTOR: u16 = 0x10;
+
+/// Operand of the `lgdt` instruction
+#[repr(C, packed)]
+struct DescriptorTablePointer {
+    limit: u16,
+    base: u64,
+}
+
+/// Loads a minimal GDT made of flat code and data segments, and reloads the
+/// segment registers from it.
+///
+/// The code segment uses selector `GDT_CODE_SELECTOR`, and all the data
+/// segment registers are set to `GDT_DATA_SELECTOR`. The GDT is allocated as
+/// `LOADER_DATA`, so a kernel which takes over the memory map must keep it
+/// around until it loads its own.
+///
+/// # Safety
+///
+/// UEFI relies on its own GDT during boot services, notably for its interrupt
+/// handlers, so this should be called late, right before handing off control
+/// to a kernel, with interrupts disabled.
+pub unsafe fn setup_minimal_gdt() -> Result {
+    let bt = crate::system_table().as_ref().boot_services();
+    let gdt = bt
+        .allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, 1)?
+        .log() as *mut u64;
+    gdt.write(0);
+    gdt.add(1).write(GDT_CODE_DESCRIPTOR);
+    gdt.add(2).write(GDT_DATA_DESCRIPTOR);
+
+    let pointer = DescriptorTablePointer {
+        limit: (3 * 8 - 1) as u16,
+        base: gdt as u64,
+    };
+    asm!(
+        "lgdt [{pointer}]",
+        // Reload CS by returning to the next instruction through a far return
+        "push {code}",
+        "lea {tmp}, [rip + 2f]",
+        "push {tmp}",
+        "retfq",
+        "2:",
+        "mov ds, ax",
+        "mov es, ax",
+        "mov fs, ax",
+        "mov gs, ax",
+        "mov ss, ax",
+        pointer = in(reg) &pointer,
+        code = in(reg) u64::from(GDT_CODE_SELECTOR),
+        tmp = lateout(reg) _,
+        in("ax") GDT_DATA_SELECTOR,
+    );
+
+    Status::SUCCESS.into()
+}
+
+/// I/O port selecting the CMOS register to access
+#[cfg(feature = "cmos_rtc")]
+const CMOS_ADDRESS_PORT: u16 = 0x70;
+
+/// I/O port through which the selected CMOS register is accessed
+#[cfg(feature = "cmos_rtc")]
+const CMOS_DATA_PORT: u16 = 0x71;
+
+/// RTC status register A, whose top bit is set while the clock is updating
+#[cfg(feature = "cmos_rtc")]
+const RTC_STATUS_A: u8 = 0x0A;
+
+/// RTC status register B, which holds the format of the time registers
+#[cfg(feature = "cmos_rtc")]
+const RTC_STATUS_B: u8 = 0x0B;
+
+/// Status register B flag: hours are in 24-hour format
+#[cfg(feature = "cmos_rtc")]
+const RTC_24_HOUR: u8 = 1 << 1;
+
+/// Status register B flag: values are in binary rather than BCD
+#[cfg(feature = "cmos_rtc")]
+const RTC_BINARY: u8 = 1 << 2;
+
+/// Reads a CMOS register
+#[cfg(feature = "cmos_rtc")]
+fn read_cmos(register: u8) -> u8 {
+    let value: u8;
+    unsafe {
+        asm!("out dx, al", in("dx") CMOS_ADDRESS_PORT, in("al") register, options(nomem, nostack, preserves_flags));
+        asm!("in al, dx", in("dx") CMOS_DATA_PORT, out("al") value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Reads the RTC time registers as (second, minute, hour, day, month, year)
+#[cfg(feature = "cmos_rtc")]
+fn read_rtc_registers() -> [u8; 6] {
+    const REGISTERS: [u8; 6] = [0x00, 0x02, 0x04, 0x07, 0x08, 0x09];
+
+    // Wait for any update in progress to complete
+    while read_cmos(RTC_STATUS_A) & 0x80 != 0 {}
+    let mut values = [0; 6];
+    for (value, &register) in values.iter_mut().zip(REGISTERS.iter()) {
+        *value = read_cmos(register);
+    }
+    values
+}
+
+/// Reads the time directly from the CMOS real time clock.
+///
+/// This is a fallback for firmware whose `get_time` runtime service is
+/// broken, and should not be preferred to it. The clock's time zone is not
+/// known, and the century is assumed to be the 21st. Fails with
+/// `DEVICE_ERROR` if the registers do not hold a valid date.
+#[cfg(feature = "cmos_rtc")]
+pub fn read_cmos_time() -> Result<Time> {
+    // The registers can change between reads, so read until they are stable
+    let mut registers = read_rtc_registers();
+    loop {
+        let again = read_rtc_registers();
+        if again == registers {
+            break;
+        }
+        registers = again;
+    }
+
+    let format = read_cmos(RTC_STATUS_B);
+    let decode = |value: u8| {
+        if format & RTC_BINARY != 0 {
+            value
+        } else {
+            (value >> 4) * 10 + (value & 0x0F)
+        }
+    };
+    let [second, minute, hour, day, month, year] = registers;
+
+    // In 12-hour format, the top bit of the hour is set after noon
+    let mut hour_value = decode(hour & 0x7F);
+    if format & RTC_24_HOUR == 0 {
+        hour_value %= 12;
+        if hour & 0x80 != 0 {
+            hour_value += 12;
+        }
+    }
+    let (second, minute, day, month) = (decode(second), decode(minute), decode(day), decode(month));
+    let year = 2000 + u16::from(decode(year));
+
+    if second > 59
+        || minute > 59
+        || hour_value > 23
+        || !(1..=31).contains(&day)
+        || !(1..=12).contains(&month)
+        || year > 2099
+    {
+        return Err(Status::DEVICE_ERROR.into());
+    }
+    Ok(Time::new(
+        year,
+        month,
+        day,
+        hour_value,
+        minute,
+        second,
+        0,
+        2047,
+        Daylight::empty(),
+    )
+    .into())
+}
+
+/// Jumps to a kernel's entry point, passing it `arg` following the System V
+/// calling convention.
+///
+/// `arg` is passed as the first argument, in `rdi`, and is usually the
+/// address of a boot information structure. If `stack` is set, the stack
+/// pointer is switched to it first, otherwise the current stack is kept. In
+/// both cases, the stack is aligned as if `entry` had been called, with a
+/// null return address to end stack traces.
+///
+/// # Safety
+///
+/// `entry` must be the address of code which never returns, and which
+/// follows the calling convention described above. `stack` must point to the
+/// end of memory which is usable as a stack. Boot services should usually
+/// have been exited beforehand.
+pub unsafe fn handoff(entry: u64, arg: u64, stack: Option<u64>) -> ! {
+    match stack {
+        Some(stack) => asm!(
+            "mov rsp, {stack}",
+            "and rsp, -16",
+            "push 0",
+            "jmp {entry}",
+            stack = in(reg) stack,
+            entry = in(reg) entry,
+            in("rdi") arg,
+            options(noreturn),
+        ),
+        None => asm!(
+            "and rsp, -16",
+            "push 0",
+            "jmp {entry}",
+            entry = in(reg) entry,
+            in("rdi") arg,
+            options(noreturn),
+        ),
+    }
+}
+
+/// Entry point of the closure run by `with_stack`, on the new stack
+extern "sysv64" fn run_closure<F: FnOnce() -> R, R>(state: &mut (Option<F>, Option<R>)) {
+    let f = state.0.take().unwrap();
+    state.1 = Some(f());
+}
+
+/// Runs `f` on a stack of `size` bytes allocated from boot services.
+///
+/// This is useful when the stack provided by the firmware, which is usually
+/// 128 KiB, is too small. The size is rounded up to whole pages, and the
+/// stack is freed once `f` returns. Fails if the stack cannot be allocated.
+pub fn with_stack<R, F: FnOnce() -> R>(size: usize, f: F) -> Result<R> {
+    let bt = unsafe { crate::system_table().as_ref().boot_services() };
+    let pages = (size as u64 + PAGE_SIZE - 1) / PAGE_SIZE;
+    let base = bt
+        .allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LOADER_DATA,
+            pages as usize,
+        )?
+        .log();
+
+    let entry = run_closure::<F, R> as extern "sysv64" fn(&mut (Option<F>, Option<R>)) as usize;
+    let mut state: (Option<F>, Option<R>) = (Some(f), None);
+
+    // The stack grows down from its page-aligned end, which provides the
+    // 16-byte alignment required at the call. The current stack pointer is
+    // kept in a callee-saved register. The target has no SSE registers, so
+    // only the general-purpose registers are clobbered by the call.
+    unsafe {
+        asm!(
+            "mov r12, rsp",
+            "mov rsp, {top}",
+            "call {entry}",
+            "mov rsp, r12",
+            top = in(reg) base + pages * PAGE_SIZE,
+            entry = in(reg) entry,
+            inout("rdi") &mut state as *mut _ => _,
+            out("r12") _,
+            out("rax") _,
+            out("rcx") _,
+            out("rdx") _,
+            out("rsi") _,
+            out("r8") _,
+            out("r9") _,
+            out("r10") _,
+            out("r11") _,
+        );
+    }
+
+    // Failing to free the stack only leaks it, the result is still valid
+    let _ = bt.free_pages(base, pages as usize);
+    Ok(state.1.take().unwrap().into())
+}
+
+/// Processor state saved by the firmware when an exception occurs
+///
+/// This is the start of `EFI_SYSTEM_CONTEXT_X64` from the PI specification,
+/// up to the registers the fault handler reports.
+#[repr(C)]
+struct SystemContext {
+    exception_data: u64,
+    fx_save_state: [u8; 512],
+    debug_registers: [u64; 6],
+    cr0: u64,
+    cr1: u64,
+    cr2: u64,
+    cr3: u64,
+    cr4: u64,
+    cr8: u64,
+    rflags: u64,
+    ldtr: u64,
+    tr: u64,
+    gdtr: [u64; 2],
+    idtr: [u64; 2],
+    rip: u64,
+    segments: [u64; 6],
+    rdi: u64,
+    rsi: u64,
+    rbp: u64,
+    rsp: u64,
+}
+
+/// Exceptions handled by `install_fault_handlers`, with their names
+const FAULT_VECTORS: [(usize, &str); 3] = [
+    (8, "double fault"),
+    (13, "general protection fault"),
+    (14, "page fault"),
+];
+
+/// Logs the state of the processor at the time of a fault, then halts
+extern "efiapi" fn dump_fault(vector: isize, context: *mut core::ffi::c_void) {
+    let context = unsafe { &*(context as *const SystemContext) };
+    let name = FAULT_VECTORS
+        .iter()
+        .find(|&&(v, _)| v as isize == vector)
+        .map_or("exception", |&(_, name)| name);
+
+    error!(
+        "Unhandled {} (vector {}), error code {:#x}",
+        name, vector, context.exception_data
+    );
+    error!(
+        "RIP {:#018x}, RSP {:#018x}, CR2 {:#018x}",
+        context.rip, context.rsp, context.cr2
+    );
+
+    crate::exit_qemu(0);
+    loop {
+        unsafe {
+            asm!("cli", "hlt", options(nomem, nostack));
+        }
+    }
+}
+
+/// Installs handlers logging page faults, general protection faults and
+/// double faults, then halting the processor.
+///
+/// The vector, error code, faulting instruction and stack pointers are
+/// logged, as well as CR2, which holds the faulting address of page faults.
+/// In QEMU with the `qemu` feature, QEMU is then exited with a failure
+/// status. This turns faults which would otherwise hang the machine or make
+/// it reset into something which can be diagnosed during bring-up.
+///
+/// The handlers go through the CPU architectural protocol, this thus fails
+/// with `UNSUPPORTED` on firmware which does not expose it, and with
+/// `ALREADY_STARTED` if one of the faults already has a handler. Note that
+/// logging from the handlers is unreliable if the fault happened while
+/// logging, and that a double fault caused by a stack overflow can only be
+/// reported if the firmware runs its handler on a separate stack.
+pub fn install_fault_handlers() -> Result {
+    for &(vector, _) in FAULT_VECTORS.iter() {
+        unsafe { crate::register_exception_handler(vector, dump_fault) }?.log();
+    }
+    Ok(().into())
+}
@@ -118,4 +118,8 @@ fn memory_map(bt: &BootServices) {
     }
     let page_count = first_desc.page_count;
     assert!(page_count != 0, "Memory map entry has zero size");
+
+    let (_base, free_pages) = uefi_services::largest_free_region()
+        .expect_success("Failed to find the largest free region");
+    assert!(free_pages != 0, "Largest free region is empty");
 }
@@ -1,11 +1,70 @@
+use core::time::Duration;
 use uefi::prelude::*;
 use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+use uefi::table::runtime::{Daylight, Time};
 
 pub fn test(bt: &BootServices) {
     info!("Testing timer...");
     test_timer(bt);
     info!("Testing watchdog...");
     test_watchdog(bt);
+    info!("Testing CRC32...");
+    test_crc32(bt);
+    info!("Testing Unix time conversion...");
+    test_unix_time();
+}
+
+fn test_unix_time() {
+    let utc = |year, month, day, hour, minute, second| {
+        Time::new(
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            0,
+            2047,
+            Daylight::empty(),
+        )
+    };
+    assert_eq!(uefi_services::unix_timestamp(&utc(1970, 1, 1, 0, 0, 0)), 0);
+    assert_eq!(
+        uefi_services::unix_timestamp(&utc(1969, 12, 31, 23, 59, 59)),
+        -1
+    );
+    assert_eq!(
+        uefi_services::unix_timestamp(&utc(2000, 3, 1, 0, 0, 0)),
+        951_868_800
+    );
+    assert_eq!(
+        uefi_services::unix_timestamp(&utc(2100, 3, 1, 0, 0, 0)),
+        4_107_542_400
+    );
+
+    // Both read the clock, which may tick in between
+    let rt = unsafe { uefi_services::system_table().as_ref().runtime_services() };
+    let time = rt.get_time().expect_success("Failed to read the clock");
+    let now = uefi_services::unix_time().expect_success("Failed to read the Unix time");
+    if time.time_zone().is_none() {
+        let expected = uefi_services::unix_timestamp(&time);
+        assert!(
+            (expected..=expected + 1).contains(&now),
+            "Unix time does not match the clock"
+        );
+    }
+}
+
+fn test_crc32(bt: &BootServices) {
+    let crc = bt
+        .calculate_crc32(b"123456789")
+        .expect_success("Failed to compute CRC32");
+    assert_eq!(crc, 0xCBF4_3926, "Wrong CRC32 check value");
+
+    assert!(
+        uefi_services::verify_system_table(),
+        "System table CRC32 mismatch"
+    );
 }
 
 fn test_watchdog(bt: &BootServices) {
@@ -22,4 +81,19 @@ fn test_timer(bt: &BootServices) {
         .expect_success("Failed to set timer");
     bt.wait_for_event(&mut events)
         .expect_success("Wait for event failed");
+
+    // The timer has expired and its signaled state was cleared
+    let signaled = uefi_services::wait_with_timeout(&timer_event, Duration::from_millis(10))
+        .expect_success("Bounded wait failed");
+    assert!(!signaled, "Expired timer was signaled again");
+    let signaled = bt
+        .check_event(timer_event)
+        .expect_success("Failed to check event");
+    assert!(!signaled, "Expired timer was signaled again");
+
+    bt.set_timer(timer_event, TimerTrigger::Relative(5_0 /*00 ns */))
+        .expect_success("Failed to set timer");
+    let signaled = uefi_services::wait_with_timeout(&timer_event, Duration::from_secs(1))
+        .expect_success("Bounded wait failed");
+    assert!(signaled, "Timer was not signaled before the timeout");
 }
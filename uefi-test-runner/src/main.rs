@@ -22,7 +22,7 @@ mod proto;
 #[entry]
 fn efi_main(image: Handle, st: SystemTable<Boot>) -> Status {
     // Initialize utilities (logging, memory allocation...)
-    uefi_services::init(&st).expect_success("Failed to initialize utilities");
+    uefi_services::init_with_image(image, &st).expect_success("Failed to initialize utilities");
 
     // Reset the console before running all the other tests.
     st.stdout()
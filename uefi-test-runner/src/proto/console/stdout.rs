@@ -47,6 +47,7 @@ fn change_color(stdout: &mut Output) {
     stdout
         .set_color(Color::White, Color::Blue)
         .expect_success("Failed to change console color");
+    assert_eq!(stdout.attribute(), 0x1F, "Wrong console attribute");
     stdout.clear().expect_success("Failed to clear screen");
 }
 
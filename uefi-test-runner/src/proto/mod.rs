@@ -12,6 +12,7 @@ pub fn test(st: &SystemTable<Boot>) {
     console::test(st);
     debug::test(bt);
     media::test(bt);
+    pci::test(bt);
     pi::test(bt);
 }
 
@@ -31,4 +32,5 @@ fn find_protocol(bt: &BootServices) {
 mod console;
 mod debug;
 mod media;
+mod pci;
 mod pi;
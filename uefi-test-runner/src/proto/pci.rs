@@ -0,0 +1,37 @@
+use uefi::prelude::*;
+use uefi::proto::pci::root_bridge::{PciAddress, PciRootBridgeIo};
+
+pub fn test(bt: &BootServices) {
+    info!("Running PCI root bridge protocol test");
+    if let Ok(bridge) = bt.locate_protocol::<PciRootBridgeIo>() {
+        let bridge = bridge.expect("Warnings encountered while opening PCI root bridge protocol");
+        let bridge = unsafe { &*bridge.get() };
+
+        let bus_range = bridge
+            .bus_range()
+            .expect_success("Failed to query the root bridge's bus range");
+        info!(
+            "- Segment {}, buses {:?}",
+            bridge.segment_number(),
+            bus_range
+        );
+
+        // The host bridge is always present as the first device of the first bus
+        let ids: u32 = bridge
+            .pci_read(PciAddress::new(*bus_range.start(), 0, 0, 0))
+            .expect_success("Failed to read the host bridge's IDs");
+        assert_ne!(ids & 0xFFFF, 0xFFFF, "Invalid host bridge vendor ID");
+
+        let ids_via_services = uefi_services::pci_config_read(
+            bridge.segment_number() as u16,
+            *bus_range.start(),
+            0,
+            0,
+            0,
+        )
+        .expect_success("Failed to read the host bridge's IDs through uefi-services");
+        assert_eq!(ids, ids_via_services);
+    } else {
+        warn!("PCI root bridge protocol is not supported");
+    }
+}